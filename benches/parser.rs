@@ -0,0 +1,79 @@
+//! Measures parsing throughput and allocations on a REPL-like workload of overlapping
+//! command lines, comparing stateless parsing, a reusable caching `Parser`, and the
+//! borrowed parser.
+//!
+//! Run with `cargo bench --bench parser`.
+
+use cmd_arg::cmd_arg::{Command, CommandRef, ParseConfig, Parser};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+const ROUNDS: usize = 2_000_000;
+
+/// Wraps the system allocator to count allocations.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Runs `parse` over `lines` for `ROUNDS` iterations and prints time and allocations.
+fn measure<F: FnMut(&[String])>(name: &str, lines: &[Vec<String>], mut parse: F) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for i in 0..ROUNDS {
+        parse(&lines[i % lines.len()]);
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    println!(
+        "{}: {:?} for {} parses ({:?} and {:.1} allocations per parse)",
+        name,
+        elapsed,
+        ROUNDS,
+        elapsed / ROUNDS as u32,
+        allocations as f64 / ROUNDS as f64
+    );
+}
+
+fn main() {
+    let templates: [&[&str]; 4] = [
+        &["repl", "--verbose", "-abc", "--data=a,b,c", "input.txt"],
+        &["repl", "--output=out.txt", "-v", "input.txt", "--", "rest"],
+        &["repl", "-abc", "--verbose", "--output=out.txt", "other.txt"],
+        &["repl", "--data=a,b,c", "-v", "--force", "input.txt"],
+    ];
+    let lines: Vec<Vec<String>> = templates
+        .iter()
+        .map(|t| t.iter().map(|s| s.to_string()).collect())
+        .collect();
+    let config = ParseConfig::default();
+
+    measure("parse_with_config", &lines, |line| {
+        black_box(Command::parse_with_config(line.iter().cloned(), &config));
+    });
+
+    let mut parser = Parser::new(config.clone());
+    measure("Parser::parse", &lines, |line| {
+        black_box(parser.parse(line.iter().cloned()));
+    });
+
+    measure("CommandRef::parse_with_config", &lines, |line| {
+        black_box(CommandRef::parse_with_config(line, &config));
+    });
+}
@@ -1,36 +1,18 @@
-use colored::Colorize;
+use colored::{ColoredString, Colorize};
+use std::collections::HashMap;
 use std::env;
+use std::ffi::OsStr;
 use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU8, Ordering};
 
-/// Represents the classification of a command-line argument based on its format.
-/// This enum distinguishes between simple arguments, short options, and long options.
-#[derive(Debug, Clone, PartialEq)]
-pub enum OptionType {
-    /// A standard positional argument or any argument that does not conform to option syntax.
-    /// Includes strings not starting with a hyphen (e.g., `input.txt`), a single hyphen (`-`),
-    /// or arguments after the `--` separator.
-    Simple,
-
-    /// An argument starting with a single hyphen followed by one or more characters (e.g., `-v`).
-    /// Supports bundled short options (e.g., `-abc` is parsed as `-a`, `-b`, `-c`).
-    ShortOpt,
-
-    /// An argument starting with two hyphens followed by one or more characters (e.g., `--verbose`).
-    /// May include values attached via an equals sign (e.g., `--output=results.txt`).
-    LongOpt,
-}
-
-/// Implements the `Default` trait for `OptionType`.
-impl Default for OptionType {
-    /// Returns the default variant of `OptionType`, which is `Simple`.
-    ///
-    /// # Returns
-    ///
-    /// `OptionType::Simple`, as it represents the most basic form of a command-line argument.
-    fn default() -> Self {
-        OptionType::Simple
-    }
-}
+#[allow(deprecated)]
+pub use crate::parse::Option;
+use crate::parse::determine_opt_type;
+pub use crate::parse::{Arg, ArgRef, Command, CommandRef, OptionRef, OptionType, ParseConfig};
 
 /// Implements the `Display` trait for `OptionType` to provide a human-readable representation.
 impl fmt::Display for OptionType {
@@ -39,6 +21,7 @@ impl fmt::Display for OptionType {
     /// - `Simple` is displayed in purple.
     /// - `ShortOpt` is displayed in yellow.
     /// - `LongOpt` is displayed in cyan.
+    /// - `PlusOpt` is displayed in green.
     ///
     /// # Arguments
     ///
@@ -48,57 +31,36 @@ impl fmt::Display for OptionType {
     ///
     /// A `Result` indicating whether the formatting was successful.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(f, colors_enabled())
+    }
+}
+
+impl OptionType {
+    /// Writes the `Display` representation, colored only if `color` is `true`.
+    fn render(&self, f: &mut fmt::Formatter<'_>, color: bool) -> fmt::Result {
         let type_str = match self {
-            OptionType::Simple => "Simple".purple(),
-            OptionType::ShortOpt => "Short Option".yellow(),
-            OptionType::LongOpt => "Long Option".cyan(),
+            OptionType::Simple => paint(self.label().purple(), color),
+            OptionType::ShortOpt => paint(self.label().yellow(), color),
+            OptionType::LongOpt => paint(self.label().cyan(), color),
+            OptionType::PlusOpt => paint(self.label().green(), color),
         };
         write!(f, "{}", type_str)
     }
-}
-
-/// Holds the parsed information for a single command-line argument classified as an option
-/// or a simple argument appearing before the `--` separator.
-#[derive(Debug, Clone)]
-pub struct Option {
-    /// The classification of this argument's format (`Simple`, `ShortOpt`, or `LongOpt`).
-    pub opt_type: OptionType,
-
-    /// The string representation of the option or simple argument.
-    /// - For `ShortOpt` (e.g., `-v`), this is the full string (e.g., `"-v"`).
-    /// - For `LongOpt` without a value (e.g., `--help`), this is the full string (e.g., `"--help"`).
-    /// - For `LongOpt` with a value (e.g., `--data=val`), this is the flag part (e.g., `"--data"`).
-    /// - For `Simple` arguments (e.g., `file.txt`), this is the full string.
-    pub opt_str: String,
-
-    /// A list of values associated with the option, if any.
-    /// - For `LongOpt` with values (e.g., `--data=v1,v2`), contains the parsed values (e.g., `["v1", "v2"]`).
-    /// - Empty for `Simple`, `ShortOpt`, or `LongOpt` without values.
-    pub opt_values: Vec<String>,
-}
 
-/// Implements the `Default` trait for `Option`.
-impl Default for Option {
-    /// Returns a default `Option` instance with empty or default values.
-    ///
-    /// # Returns
-    ///
-    /// An `Option` with:
-    /// - `opt_type`: `OptionType::Simple` (via `OptionType::default()`).
-    /// - `opt_str`: An empty string.
-    /// - `opt_values`: An empty vector.
-    fn default() -> Self {
-        Option {
-            opt_type: OptionType::default(),
-            opt_str: String::new(),
-            opt_values: Vec::new(),
+    /// Returns the uncolored name used when displaying this variant (e.g., `"Short Option"`).
+    fn label(&self) -> &'static str {
+        match self {
+            OptionType::Simple => "Simple",
+            OptionType::ShortOpt => "Short Option",
+            OptionType::LongOpt => "Long Option",
+            OptionType::PlusOpt => "Plus Option",
         }
     }
 }
 
-/// Implements the `Display` trait for `Option` to provide a human-readable representation.
-impl fmt::Display for Option {
-    /// Formats the `Option` struct as a colored string for console output.
+/// Implements the `Display` trait for `Arg` to provide a human-readable representation.
+impl fmt::Display for Arg {
+    /// Formats the `Arg` struct as a colored string for console output.
     ///
     /// - The `opt_str` is displayed in magenta.
     /// - The `opt_type` is formatted using its own `Display` implementation.
@@ -114,60 +76,192 @@ impl fmt::Display for Option {
     ///
     /// # Example
     ///
-    /// An `Option` with `opt_str = "--data"`, `opt_type = LongOpt`, and `opt_values = ["v1", "v2"]`
-    /// might be formatted as:
+    /// An `Arg` with `opt_str = "--data"`, `opt_type = LongOpt`, and `opt_values = ["v1", "v2"]`
+    /// is formatted as follows (shown without colors via [`Arg::to_plain_string`]):
     /// ```
-    /// --data (Type: Long Option): Values: [v1, v2]
+    /// use cmd_arg::cmd_arg::Command;
+    ///
+    /// let command = Command::parse_from(["prog", "--data=v1,v2"]);
+    /// assert_eq!(
+    ///     command.opts[0].to_plain_string(),
+    ///     "--data (Type: Long Option): Values: [v1, v2]"
+    /// );
     /// ```
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(f, colors_enabled())
+    }
+}
+
+impl Arg {
+    /// Renders the same layout as `Display`, but never with ANSI escapes.
+    ///
+    /// Unlike `format!("{}", arg)`, the result does not depend on [`set_color_mode`] or the
+    /// environment, which makes it suitable for log lines and error messages.
+    ///
+    /// # Returns
+    ///
+    /// The uncolored representation, such as `--data (Type: Long Option): Values: [v1, v2]`.
+    pub fn to_plain_string(&self) -> String {
+        Render(|f: &mut fmt::Formatter<'_>| self.render(f, false)).to_string()
+    }
+
+    /// Writes the `Display` representation, colored only if `color` is `true`.
+    fn render(&self, f: &mut fmt::Formatter<'_>, color: bool) -> fmt::Result {
         let values = if self.opt_values.is_empty() {
-            "None".red().to_string()
+            paint("None".red(), color).to_string()
         } else {
-            format!("[{}]", self.opt_values.join(", ").green())
+            format!("[{}]", paint(self.opt_values.join(", ").green(), color))
         };
         write!(
             f,
-            "{} ({}: {}): {}: {}",
-            self.opt_str.magenta(),
-            "Type".cyan(),
-            self.opt_type,
-            "Values".cyan(),
-            values
-        )
+            "{} ({}: ",
+            paint(self.opt_str.magenta(), color),
+            paint("Type".cyan(), color)
+        )?;
+        self.opt_type.render(f, color)?;
+        write!(f, "): {}: {}", paint("Values".cyan(), color), values)
+    }
+}
+
+/// Adapts a rendering closure to `Display`, so it can be turned into a `String`.
+struct Render<F>(F);
+
+impl<F: Fn(&mut fmt::Formatter<'_>) -> fmt::Result> fmt::Display for Render<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.0)(f)
+    }
+}
+
+/// Strips the colors and styles from `text` unless `color` is `true`.
+///
+/// Every colored fragment written by the `Display` implementations goes through this
+/// function with the result of `colors_enabled`, so piping a program's output (e.g.,
+/// `program --data=a,b | cat`) yields plain text.
+fn paint(text: ColoredString, color: bool) -> ColoredString {
+    if color { text } else { text.clear() }
+}
+
+/// Controls whether the `Display` implementations emit ANSI colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    /// Color only when `NO_COLOR` is unset (or empty) and standard output is a terminal.
+    Auto,
+    /// Always color, regardless of the environment.
+    Always,
+    /// Never color.
+    Never,
+}
+
+/// The current `ColorMode`, stored as its discriminant so it is cheap to read on every format call.
+static COLOR_MODE: AtomicU8 = AtomicU8::new(ColorMode::Auto as u8);
+
+/// Sets how the `Display` implementations of `OptionType`, `Arg`, and `Command` color output.
+///
+/// An explicit `Always` or `Never` takes precedence over the automatic `NO_COLOR` and
+/// terminal detection used by `Auto` (the default). The setting is process-wide and
+/// thread-safe. `Always` also overrides the `colored` crate's own detection, via
+/// `colored::control::set_override`, until another mode is set.
+///
+/// # Arguments
+///
+/// * `mode` - The coloring mode to use from now on.
+pub fn set_color_mode(mode: ColorMode) {
+    let previous = COLOR_MODE.swap(mode as u8, Ordering::Relaxed);
+    if mode == ColorMode::Always {
+        colored::control::set_override(true);
+    } else if previous == ColorMode::Always as u8 {
+        colored::control::unset_override();
     }
 }
 
-/// Represents the complete structured result of parsing the command line.
-/// It separates the program name, options/initial simple arguments, and arguments after `--`.
-#[derive(Debug)]
-pub struct Command {
-    /// The name of the executable program, typically the first argument from the environment.
-    pub cmd_name: String,
+/// Turns colored output on or off explicitly.
+///
+/// Shorthand for [`set_color_mode`] with `ColorMode::Always` or `ColorMode::Never`. After
+/// `set_colored(false)`, `format!("{}", command)` returns plain text without escape codes,
+/// which suits destinations such as GUI log widgets that do not interpret ANSI.
+///
+/// # Arguments
+///
+/// * `enabled` - Whether to emit colors.
+pub fn set_colored(enabled: bool) {
+    set_color_mode(if enabled {
+        ColorMode::Always
+    } else {
+        ColorMode::Never
+    });
+}
 
-    /// A vector of parsed options (`ShortOpt`, `LongOpt`) and simple arguments before `--`.
-    pub opts: Vec<Option>,
+/// Returns whether the `Display` implementations should emit ANSI colors.
+///
+/// Follows the mode chosen with [`set_color_mode`]. In `Auto` mode, the answer is detected
+/// once per process from the `NO_COLOR` environment variable and whether standard output
+/// is a terminal; see [`detect_colors`].
+fn colors_enabled() -> bool {
+    static DETECTED: OnceLock<bool> = OnceLock::new();
+    match COLOR_MODE.load(Ordering::Relaxed) {
+        mode if mode == ColorMode::Always as u8 => true,
+        mode if mode == ColorMode::Never as u8 => false,
+        _ => *DETECTED.get_or_init(|| {
+            detect_colors(
+                env::var_os("NO_COLOR").as_deref(),
+                io::stdout().is_terminal(),
+            )
+        }),
+    }
+}
 
-    /// A vector of simple arguments appearing after the `--` separator.
-    pub args: Vec<String>,
+/// Decides whether colors should be used.
+///
+/// Colors are disabled when `NO_COLOR` is set to a non-empty value (see
+/// <https://no-color.org>) or when standard output is not a terminal.
+///
+/// # Arguments
+///
+/// * `no_color` - The value of the `NO_COLOR` environment variable, if set.
+/// * `is_terminal` - Whether standard output is a terminal.
+fn detect_colors(no_color: std::option::Option<&OsStr>, is_terminal: bool) -> bool {
+    let no_color = no_color.is_some_and(|value| !value.is_empty());
+    !no_color && is_terminal
 }
 
-/// Implements the `Default` trait for `Command`.
-impl Default for Command {
-    /// Returns a default `Command` instance with empty fields.
+impl Arg {
+    /// Checks whether this option was given under `name`.
     ///
-    /// # Returns
+    /// A `name` with leading hyphens or a leading `+` (e.g., `"--data"`, `"-v"`, or `"+a"`)
+    /// must equal `opt_str` exactly, so a bare `name` never matches a `PlusOpt`.
+    /// A bare `name` (e.g., `"data"` or `"v"`) matches a `ShortOpt` or `LongOpt` whose
+    /// `opt_str` equals it once the leading hyphens are removed. `Simple` arguments never match.
     ///
-    /// A `Command` with:
-    /// - `cmd_name`: An empty string.
-    /// - `opts`: An empty vector.
-    /// - `args`: An empty vector.
-    fn default() -> Self {
-        Command {
-            cmd_name: String::new(),
-            opts: Vec::new(),
-            args: Vec::new(),
+    /// # Arguments
+    ///
+    /// * `name` - The flag name to compare against, with or without leading hyphens.
+    fn matches(&self, name: &str) -> bool {
+        self.matches_by(name, |a, b| a == b)
+    }
+
+    /// Like [`Arg::matches`], but compares names with ASCII case folding.
+    fn matches_ci(&self, name: &str) -> bool {
+        self.matches_by(name, str::eq_ignore_ascii_case)
+    }
+
+    /// Applies the [`Arg::matches`] rules using `eq` to compare the names.
+    fn matches_by(&self, name: &str, eq: fn(&str, &str) -> bool) -> bool {
+        match self.opt_type {
+            OptionType::Simple => false,
+            _ if name.starts_with(['-', '+']) => eq(&self.opt_str, name),
+            _ => eq(self.opt_str.trim_start_matches('-'), name),
         }
     }
+
+    /// Checks whether this option is a conventional help request (`-h` or `--help`).
+    fn is_help(&self) -> bool {
+        self.matches("-h") || self.matches("--help")
+    }
+
+    /// Checks whether this option is a conventional version request (`-V` or `--version`).
+    fn is_version(&self) -> bool {
+        self.matches("-V") || self.matches("--version")
+    }
 }
 
 /// Implements the `Display` trait for `Command` to provide a human-readable representation.
@@ -177,6 +271,8 @@ impl fmt::Display for Command {
     /// - Displays the command name in blue with a bold cyan "Command" label.
     /// - Lists all options (from `opts`) with their type and values, or indicates none were found.
     /// - Lists all arguments after `--` (from `args`), or indicates none were found.
+    /// - Lists the argument groups (from `arg_groups`), if any.
+    /// - Lists the annotations (from `annotations`), if any.
     ///
     /// # Arguments
     ///
@@ -188,215 +284,4857 @@ impl fmt::Display for Command {
     ///
     /// # Example
     ///
-    /// For a command like `program -v file.txt --data=apple,banana -- positional1`, the output might be:
+    /// For a command like `program -v file.txt --data=apple,banana -- positional1`, the output
+    /// is as follows (shown without colors via [`Command::to_plain_string`]):
     /// ```
-    /// Command: program
-    /// Options:
-    ///   1. -v (Type: Short Option): Values: None
-    ///   2. file.txt (Type: Simple): Values: None
-    ///   3. --data (Type: Long Option): Values: [apple, banana]
-    /// Arguments (-- after):
-    ///   1. positional1
+    /// use cmd_arg::cmd_arg::Command;
+    ///
+    /// let command = Command::parse_from(
+    ///     ["program", "-v", "file.txt", "--data=apple,banana", "--", "positional1"],
+    /// );
+    /// let output = command.to_plain_string();
+    /// assert_eq!(
+    ///     output.lines().collect::<Vec<_>>(),
+    ///     [
+    ///         "Command: program",
+    ///         "Options:",
+    ///         "  1. -v (Type: Short Option): Values: None",
+    ///         "  2. file.txt (Type: Simple): Values: None",
+    ///         "  3. --data (Type: Long Option): Values: [apple, banana]",
+    ///         "Arguments (-- after):",
+    ///         "  1. positional1",
+    ///     ]
+    /// );
     /// ```
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{}: {}", "Command".cyan().bold(), self.cmd_name.blue())?;
-        writeln!(f, "{}:", "Options".green().bold())?;
+        self.render(f, colors_enabled())
+    }
+}
+
+impl Command {
+    /// Renders the same layout as `Display`, but never with ANSI escapes.
+    ///
+    /// Unlike `format!("{}", command)`, the result does not depend on [`set_color_mode`] or
+    /// the environment, which makes it suitable for log lines and error messages.
+    ///
+    /// # Returns
+    ///
+    /// The uncolored multi-line representation.
+    pub fn to_plain_string(&self) -> String {
+        Render(|f: &mut fmt::Formatter<'_>| self.render(f, false)).to_string()
+    }
+
+    /// Writes the `Display` representation, colored only if `color` is `true`.
+    fn render(&self, f: &mut fmt::Formatter<'_>, color: bool) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {}",
+            paint("Command".cyan().bold(), color),
+            paint(self.cmd_name.blue(), color)
+        )?;
+        writeln!(f, "{}:", paint("Options".green().bold(), color))?;
         if self.opts.is_empty() {
-            writeln!(f, "  {}", "No Options provided (before --).".red())?;
+            writeln!(
+                f,
+                "  {}",
+                paint("No Options provided (before --).".red(), color)
+            )?;
         } else {
             for (i, opt) in self.opts.iter().enumerate() {
-                writeln!(f, "  {}. {}", (i + 1).to_string().bold(), opt)?;
+                write!(f, "  {}. ", paint((i + 1).to_string().bold(), color))?;
+                opt.render(f, color)?;
+                writeln!(f)?;
             }
         }
-        writeln!(f, "{}:", "Arguments (-- after)".green().bold())?;
+        writeln!(
+            f,
+            "{}:",
+            paint("Arguments (-- after)".green().bold(), color)
+        )?;
         if self.args.is_empty() {
-            writeln!(f, "  {}", "No arguments provided after --.".red())?;
+            writeln!(
+                f,
+                "  {}",
+                paint("No arguments provided after --.".red(), color)
+            )?;
         } else {
             for (i, arg) in self.args.iter().enumerate() {
-                writeln!(f, "  {}. {}", (i + 1).to_string().bold(), arg.blue())?;
+                writeln!(
+                    f,
+                    "  {}. {}",
+                    paint((i + 1).to_string().bold(), color),
+                    paint(arg.blue(), color)
+                )?;
+            }
+        }
+        if !self.arg_groups.is_empty() {
+            writeln!(f, "{}:", paint("Argument Groups".green().bold(), color))?;
+            for (i, group) in self.arg_groups.iter().enumerate() {
+                writeln!(
+                    f,
+                    "  {}. [{}]",
+                    paint((i + 1).to_string().bold(), color),
+                    paint(group.join(", ").blue(), color)
+                )?;
+            }
+        }
+        if !self.annotations.is_empty() {
+            writeln!(f, "{}:", paint("Annotations".green().bold(), color))?;
+            for (i, note) in self.annotations.iter().enumerate() {
+                writeln!(
+                    f,
+                    "  {}. {}",
+                    paint((i + 1).to_string().bold(), color),
+                    paint(note.blue(), color)
+                )?;
             }
         }
         Ok(())
     }
 }
 
-impl Command {
-    /// Creates a new `Command` instance with the specified command name and empty vectors.
+/// The largest number of tokens a [`Parser`] keeps classified before clearing its cache.
+const PARSER_CACHE_CAPACITY: usize = 1024;
+
+/// A reusable parser for hot loops, such as a REPL, that parse many similar command lines.
+///
+/// A `Parser` holds a [`ParseConfig`] and remembers the [`OptionType`] of every token it has
+/// classified, so a token seen in an earlier command line is looked up instead of being
+/// classified again. The cache only changes how a result is computed: [`Parser::parse`]
+/// always returns exactly what [`Command::parse_with_config`] returns for the same
+/// configuration. The cache holds up to 1024 tokens and is cleared when it fills up, so a
+/// long-lived `Parser` fed with ever-new tokens does not grow without bound.
+///
+/// # Examples
+///
+/// ```
+/// use cmd_arg::cmd_arg::{ParseConfig, Parser};
+///
+/// let mut parser = Parser::new(ParseConfig::default());
+/// for line in [["repl", "-v", "--data=a,b"], ["repl", "--data=a,b", "file.txt"]] {
+///     let command = parser.parse(line);
+///     assert_eq!(command.cmd_name, "repl");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Parser {
+    /// The settings every parse uses.
+    config: ParseConfig,
+
+    /// The classification of each token seen so far.
+    cache: HashMap<String, OptionType>,
+}
+
+/// Implements the `Default` trait for `Parser`.
+impl Default for Parser {
+    /// Returns a `Parser` using the default [`ParseConfig`], with an empty cache.
+    fn default() -> Self {
+        Parser::new(ParseConfig::default())
+    }
+}
+
+impl Parser {
+    /// Creates a parser with an empty cache.
     ///
     /// # Arguments
     ///
-    /// * `cmd_name` - The name of the command (e.g., the program name).
+    /// * `config` - The settings every parse uses.
     ///
     /// # Returns
     ///
-    /// A `Command` instance with the given `cmd_name`, and empty `opts` and `args` vectors.
-    fn new(cmd_name: String) -> Self {
-        Command {
-            cmd_name,
-            opts: Vec::new(),
-            args: Vec::new(),
+    /// A `Parser` ready to parse command lines with `config`.
+    pub fn new(config: ParseConfig) -> Self {
+        Parser {
+            config,
+            cache: HashMap::new(),
         }
     }
 
-    /// Adds a parsed `Option` to the internal `opts` vector.
+    /// Returns the settings this parser uses.
+    pub fn config(&self) -> &ParseConfig {
+        &self.config
+    }
+
+    /// Parses a sequence of arguments, reusing the classification of tokens seen before.
     ///
     /// # Arguments
     ///
-    /// * `opt` - The `Option` struct to add, representing a parsed argument before `--`.
-    fn add_opt(&mut self, opt: Option) {
-        self.opts.push(opt);
-    }
-}
-
-/// Determines the classification of a command-line argument based on its format.
-///
-/// This function is used for arguments before the `--` separator. Arguments after `--`
-/// are always treated as `Simple` and are not passed to this function.
-///
-/// # Arguments
-///
-/// * `arg` - The command-line argument to classify.
-///
-/// # Returns
-///
-/// An `OptionType` indicating whether the argument is `Simple`, `ShortOpt`, or `LongOpt`.
-///
-/// # Examples
-///
-/// ```
-/// assert_eq!(determine_opt_type("file.txt"), OptionType::Simple);
-/// assert_eq!(determine_opt_type("-v"), OptionType::ShortOpt);
-/// assert_eq!(determine_opt_type("--verbose"), OptionType::LongOpt);
-/// assert_eq!(determine_opt_type("-"), OptionType::Simple);
-/// ```
-fn determine_opt_type(arg: &str) -> OptionType {
-    if arg.starts_with("--") {
-        OptionType::LongOpt
-    } else if arg.starts_with("-") && arg.len() > 1 {
-        OptionType::ShortOpt
-    } else {
-        OptionType::Simple
+    /// * `args` - The arguments to parse, including the command name.
+    ///
+    /// # Returns
+    ///
+    /// The same `Command` that [`Command::parse_with_config`] returns for `args` and this
+    /// parser's configuration.
+    pub fn parse<I, S>(&mut self, args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let config = &self.config;
+        let cache = &mut self.cache;
+        Command::parse_classified(args, config, |arg| {
+            if let Some(opt_type) = cache.get(arg) {
+                return opt_type.clone();
+            }
+            let opt_type = config.classify(arg);
+            if cache.len() >= PARSER_CACHE_CAPACITY {
+                cache.clear();
+            }
+            cache.insert(arg.to_string(), opt_type.clone());
+            opt_type
+        })
     }
 }
 
-/// Parses a comma-separated string of values into a vector of trimmed strings.
-///
-/// Used for processing values in long options with `=` (e.g., `--data=v1,v2`).
-/// Empty strings (e.g., from `,,` or trailing commas) are filtered out.
-///
-/// # Arguments
-///
-/// * `value` - The string containing comma-separated values.
-///
-/// # Returns
-///
-/// A `Vec<String>` of trimmed, non-empty values. Returns an empty vector if the input is empty or contains only whitespace/commas.
-///
-/// # Examples
-///
-/// ```
-/// assert_eq!(parse_values("v1,v2"), vec!["v1", "v2"]);
-/// assert_eq!(parse_values("v1, v2, "), vec!["v1", "v2"]);
-/// assert_eq!(parse_values(""), vec![] as Vec<String>);
-/// ```
-fn parse_values(value: &str) -> Vec<String> {
-    value
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect()
-}
-
-/// Parses the command-line arguments from the environment into a structured `Command`.
-///
-/// The first argument is the command name. Arguments before `--` are parsed as options
-/// or simple arguments and stored in `opts`. Arguments after `--` are stored in `args`.
-///
-/// # Returns
-///
-/// A `Command` struct containing:
-/// - The command name.
-/// - A vector of parsed options/simple arguments before `--`.
-/// - A vector of arguments after `--`.
-///
-/// # Examples
-///
-/// For a command like `program -iv file.txt --data=apple,banana --verbose -- positional1 --pos-flag`:
-/// - `cmd_name` will be `"program"`.
-/// - `opts` will include `-i`, `-v`, `file.txt`, `--data` (with values `["apple", "banana"]`), and `--verbose`.
-/// - `args` will include `["positional1", "--pos-flag"]`.
-pub fn get() -> Command {
-    let mut args_iter = env::args();
-    let cmd_name = args_iter.next().unwrap_or_default();
-    let mut command = Command::new(cmd_name);
-
-    while let Some(arg) = args_iter.next() {
-        if arg == "--" {
-            command.args.extend(args_iter);
-            break;
-        }
-
-        let opt_type = determine_opt_type(&arg);
-
-        match opt_type {
-            OptionType::LongOpt => {
-                if let Some((key, value)) = arg.split_once('=') {
-                    let opt_values = parse_values(value);
-                    command.add_opt(Option {
-                        opt_type: OptionType::LongOpt,
-                        opt_str: key.to_string(),
-                        opt_values,
-                    });
-                } else {
-                    command.add_opt(Option {
-                        opt_type: OptionType::LongOpt,
-                        opt_str: arg,
-                        opt_values: Vec::new(),
-                    });
+impl Command {
+    /// Parses arguments like [`Command::parse_with_config`], then enforces the constraints in
+    /// `config` that can reject input.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    /// * `config` - The settings controlling classification and validation.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Command`, or the first `ParseError` found:
+    /// - `ParseError::EmptyLongOptionName` for a long option with nothing after `--`, such
+    ///   as `--=foo`.
+    /// - `ParseError::MalformedOption` for a long option whose name starts with a further
+    ///   hyphen, such as `---verbose`.
+    /// - `ParseError::UnexpectedValue` if `config.long_values_require_equals` is disabled and
+    ///   a long option before `--` is written as `--flag=value`.
+    /// - `ParseError::TooFewValues` if a flag has fewer values than `config.min_values` requires.
+    pub fn try_parse_with_config<I, S>(args: I, config: &ParseConfig) -> Result<Command, ParseError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let args: Vec<String> = args.into_iter().map(Into::into).collect();
+        let long_opts = args
+            .iter()
+            .skip(1)
+            .take_while(|arg| !config.is_separator(arg))
+            .filter(|arg| *arg != "--" && determine_opt_type(arg) == OptionType::LongOpt);
+        for arg in long_opts {
+            let (flag, has_value) = config.long_flag(arg);
+            match flag.get(2..).unwrap_or_default() {
+                "" => return Err(ParseError::EmptyLongOptionName(arg.clone())),
+                name if name.starts_with('-') => {
+                    return Err(ParseError::MalformedOption(arg.clone()));
                 }
-            }
-            OptionType::ShortOpt => {
-                if arg.len() > 2 {
-                    for c in arg.chars().skip(1) {
-                        command.add_opt(Option {
-                            opt_type: OptionType::ShortOpt,
-                            opt_str: format!("-{}", c),
-                            opt_values: Vec::new(),
-                        });
-                    }
-                } else {
-                    command.add_opt(Option {
-                        opt_type: OptionType::ShortOpt,
-                        opt_str: arg,
-                        opt_values: Vec::new(),
-                    });
+                _ if has_value && !config.long_values_require_equals => {
+                    return Err(ParseError::UnexpectedValue(flag.to_string()));
                 }
+                _ => {}
+            }
+        }
+
+        let command = Command::parse_with_config(args, config);
+        for opt in &command.opts {
+            if let Some((_, need)) = config
+                .min_values
+                .iter()
+                .find(|(name, need)| opt.matches(name) && opt.opt_values.len() < *need)
+            {
+                return Err(ParseError::TooFewValues {
+                    flag: opt.opt_str.clone(),
+                    got: opt.opt_values.len(),
+                    need: *need,
+                });
             }
-            OptionType::Simple => {
-                command.add_opt(Option {
+        }
+        Ok(command)
+    }
+
+    /// Returns the first value given to any of several equivalent flag names.
+    ///
+    /// Options are scanned in parse order, so with aliases such as `--out`, `--output`,
+    /// and `-o`, whichever valued alias appeared first on the command line wins, regardless
+    /// of the order of `names`. Occurrences without values are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `names` - The accepted spellings of the flag (e.g., `&["--output", "--out", "-o"]`).
+    ///
+    /// # Returns
+    ///
+    /// The first value found, or `None` if no alias carries a value.
+    pub fn first_value_of_any(&self, names: &[&str]) -> std::option::Option<&str> {
+        self.opts
+            .iter()
+            .filter(|opt| names.iter().any(|name| opt.matches(name)))
+            .find_map(|opt| opt.opt_values.first())
+            .map(String::as_str)
+    }
+
+    /// Iterates over the parsed options in reverse parse order.
+    ///
+    /// This suits "last wins" lookups that should stop at the final occurrence of a flag.
+    ///
+    /// # Returns
+    ///
+    /// An iterator yielding references to the entries of `opts`, last first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::{Command, ParseConfig};
+    ///
+    /// let command = Command::parse_with_config(
+    ///     ["prog", "--mode=a", "-v", "--mode=b"],
+    ///     &ParseConfig::default(),
+    /// );
+    /// let last_mode = command.opts_rev().find(|o| o.opt_str == "--mode").unwrap();
+    /// assert_eq!(last_mode.opt_values, ["b"]);
+    /// ```
+    pub fn opts_rev(&self) -> impl Iterator<Item = &Arg> {
+        self.opts.iter().rev()
+    }
+
+    /// Splits off a git-style subcommand, such as `commit` in `tool -v commit --amend`.
+    ///
+    /// The subcommand is the first `Simple` entry of `opts`. Options before it are global
+    /// flags and stay with the parent, so they are not part of the result. The returned
+    /// `Command` has the subcommand as its `cmd_name`, the entries after it as its `opts`,
+    /// and the same trailing `args`.
+    ///
+    /// # Returns
+    ///
+    /// The subcommand name and its own `Command`, or `None` if `opts` holds no `Simple`
+    /// entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::Command;
+    ///
+    /// let command = Command::parse_from(["tool", "-v", "commit", "-m=msg"]);
+    /// let (name, sub) = command.subcommand().unwrap();
+    /// assert_eq!(name, "commit");
+    /// assert_eq!(sub.opts[0].opt_str, "-m");
+    /// assert_eq!(sub.opts[0].opt_values, ["msg"]);
+    /// ```
+    pub fn subcommand(&self) -> std::option::Option<(&str, Command)> {
+        let index = self
+            .opts
+            .iter()
+            .position(|o| o.opt_type == OptionType::Simple)?;
+        let name = self.opts[index].opt_str.as_str();
+        let mut sub = Command::new(name.to_string());
+        sub.opts = self.opts[index + 1..].to_vec();
+        sub.args = self.args.clone();
+        Some((name, sub))
+    }
+
+    /// Inserts or replaces the subcommand name, e.g. to rewrite an alias such as `ci` to `check`.
+    ///
+    /// The subcommand is the leading `Simple` entry of `opts`. If the first option is
+    /// `Simple`, its `opt_str` is replaced with `name`; otherwise a new `Simple` entry is
+    /// prepended. Any values held by a replaced entry are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The subcommand name to set.
+    pub fn set_subcommand(&mut self, name: &str) {
+        match self.opts.first_mut() {
+            Some(first) if first.opt_type == OptionType::Simple => first.opt_str = name.to_string(),
+            _ => self.opts.insert(
+                0,
+                Arg {
                     opt_type: OptionType::Simple,
-                    opt_str: arg,
+                    opt_str: name.to_string(),
                     opt_values: Vec::new(),
-                });
+                    append: false,
+                    origin_index: 0,
+                },
+            ),
+        }
+    }
+
+    /// Removes a namespace prefix from matching long option names.
+    ///
+    /// Only the part after the leading `--` is considered, so with `prefix = "myapp-"`,
+    /// `--myapp-verbose` becomes `--verbose`. Options that do not start with the prefix,
+    /// short options, simple arguments, and names that would become empty are left alone.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The prefix to remove from long option names (without `--`).
+    pub fn strip_flag_prefix(&mut self, prefix: &str) {
+        for opt in self
+            .opts
+            .iter_mut()
+            .filter(|o| o.opt_type == OptionType::LongOpt)
+        {
+            if let Some(rest) = opt
+                .opt_str
+                .strip_prefix("--")
+                .and_then(|name| name.strip_prefix(prefix))
+                .filter(|rest| !rest.is_empty())
+            {
+                opt.opt_str = format!("--{}", rest);
             }
         }
     }
 
-    command
-}
+    /// Checks that every flag in `opts` is one of the `allowed` names.
+    ///
+    /// Names are matched as described for flag lookups: `"--verbose"` must match exactly,
+    /// while a bare `"verbose"` also matches `--verbose`. `Simple` arguments are not checked.
+    ///
+    /// # Arguments
+    ///
+    /// * `allowed` - The flag names the program understands.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if all flags are known, or `Err` with the `opt_str` of each unknown flag in
+    /// parse order.
+    pub fn validate_known(&self, allowed: &[&str]) -> Result<(), Vec<String>> {
+        let unknown: Vec<String> = self
+            .unknown_options(allowed)
+            .into_iter()
+            .map(|o| o.opt_str.clone())
+            .collect();
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(unknown)
+        }
+    }
 
-/// Retrieves the complete command line as a single string, including the command name and all arguments.
-///
-/// # Returns
-///
-/// A `String` containing all command-line arguments joined by spaces.
-///
-/// # Examples
-///
-/// For a command like `program -v file.txt`, this returns:
-/// ```
-/// "program -v file.txt"
-/// ```
-pub fn cmd_str() -> String {
-    env::args().collect::<Vec<String>>().join(" ")
+    /// Returns every option in `opts` that is not one of the `known` names.
+    ///
+    /// Names are matched as in [`Command::validate_known`]: a bare name such as `"verbose"`
+    /// covers `--verbose` (and `"v"` covers `-v`) regardless of hyphens, while a hyphenated
+    /// name must match exactly. `Simple` arguments are never reported. Pair the result with
+    /// [`suggest`] to warn about typos such as `--verbsoe`.
+    ///
+    /// # Arguments
+    ///
+    /// * `known` - The flag names the program understands.
+    ///
+    /// # Returns
+    ///
+    /// The unknown options in parse order, or an empty vector if all are known.
+    pub fn unknown_options(&self, known: &[&str]) -> Vec<&Arg> {
+        self.opts
+            .iter()
+            .filter(|o| o.opt_type != OptionType::Simple)
+            .filter(|o| !known.iter().any(|name| o.matches(name)))
+            .collect()
+    }
+
+    /// Panics with a descriptive message if any flag is not one of the `allowed` names.
+    ///
+    /// This is a convenience for binaries that want to stop on a mistyped flag directly in
+    /// `main`. The message lists every unknown flag along with a "did you mean" hint from
+    /// [`suggest`] where one is available. Libraries should call [`Command::validate_known`]
+    /// instead and report the error themselves.
+    ///
+    /// # Arguments
+    ///
+    /// * `allowed` - The flag names the program understands.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`Command::validate_known`] reports any unknown flag.
+    pub fn expect_known(&self, allowed: &[&str]) {
+        if let Err(unknown) = self.validate_known(allowed) {
+            let described: Vec<String> = unknown
+                .iter()
+                .map(|flag| match suggest(flag, allowed) {
+                    Some(hint) => format!("`{}` (did you mean `{}`?)", flag, hint),
+                    None => format!("`{}`", flag),
+                })
+                .collect();
+            panic!("unknown option(s): {}", described.join(", "));
+        }
+    }
+
+    /// Renders the parse result as a TOML document, e.g. to persist an invocation as a config file.
+    ///
+    /// - Long options go into an `[options]` table keyed by their name without `--`.
+    ///   A single value becomes a string, several values (including values merged from
+    ///   repeated flags) become an array, and a valueless flag becomes `true`.
+    /// - `Simple` arguments before `--` become the `positionals` array.
+    /// - Arguments after `--` become the `args` array.
+    ///
+    /// Short options are not included. Requires the `toml` feature.
+    ///
+    /// # Returns
+    ///
+    /// A `String` containing the TOML document.
+    ///
+    /// # Example
+    ///
+    /// For `program --out=a.txt --tag=x,y --force input.txt`, the output is:
+    /// ```
+    /// use cmd_arg::cmd_arg::Command;
+    ///
+    /// let command =
+    ///     Command::parse_from(["program", "--out=a.txt", "--tag=x,y", "--force", "input.txt"]);
+    /// assert_eq!(
+    ///     command.to_toml(),
+    ///     r#"args = []
+    /// positionals = ["input.txt"]
+    ///
+    /// [options]
+    /// force = true
+    /// out = "a.txt"
+    /// tag = ["x", "y"]
+    /// "#
+    /// );
+    /// ```
+    ///
+    /// Keys are emitted in sorted order.
+    #[cfg(feature = "toml")]
+    pub fn to_toml(&self) -> String {
+        use toml::{Table, Value};
+
+        let mut values: Vec<(&str, Vec<&String>)> = Vec::new();
+        for opt in self
+            .opts
+            .iter()
+            .filter(|o| o.opt_type == OptionType::LongOpt)
+        {
+            let name = opt.opt_str.trim_start_matches('-');
+            match values.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, existing)) => existing.extend(&opt.opt_values),
+                None => values.push((name, opt.opt_values.iter().collect())),
+            }
+        }
+
+        let mut options = Table::new();
+        for (name, vals) in values {
+            let value = match vals.as_slice() {
+                [] => Value::Boolean(true),
+                [single] => Value::String(single.to_string()),
+                many => Value::Array(many.iter().map(|v| Value::String(v.to_string())).collect()),
+            };
+            options.insert(name.to_string(), value);
+        }
+
+        let positionals = self
+            .opts
+            .iter()
+            .filter(|o| o.opt_type == OptionType::Simple)
+            .map(|o| Value::String(o.opt_str.clone()))
+            .collect();
+        let args = self.args.iter().cloned().map(Value::String).collect();
+
+        let mut root = Table::new();
+        root.insert("positionals".to_string(), Value::Array(positionals));
+        root.insert("args".to_string(), Value::Array(args));
+        root.insert("options".to_string(), Value::Table(options));
+        root.to_string()
+    }
+
+    /// Consumes the `Command` and collects option values into a map keyed by `opt_str`.
+    ///
+    /// Values are moved rather than cloned. Repeated flags have their values concatenated
+    /// in parse order, flags without values map to an empty vector, and `Simple` arguments
+    /// and trailing `args` are dropped. The `Command` cannot be used afterwards.
+    ///
+    /// # Returns
+    ///
+    /// A `HashMap` from each flag's `opt_str` (e.g., `"--include"`) to its values.
+    pub fn into_value_map(self) -> HashMap<String, Vec<String>> {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        for opt in self.opts {
+            if opt.opt_type != OptionType::Simple {
+                map.entry(opt.opt_str).or_default().extend(opt.opt_values);
+            }
+        }
+        map
+    }
+
+    /// Checks whether the invocation is pure passthrough, i.e. `--` came first.
+    ///
+    /// # Returns
+    ///
+    /// `true` if `opts` is empty but `args` is not, as for `prog -- a -b`.
+    pub fn all_trailing(&self) -> bool {
+        self.opts.is_empty() && !self.args.is_empty()
+    }
+
+    /// Applies default flags written as a command-line string, keeping flags already present.
+    ///
+    /// `line` is split with [`shell_split`] and parsed with the default [`ParseConfig`]
+    /// (it should not include a command name). Each of its flags whose `opt_str` does not
+    /// already appear in `opts` is appended, so flags given on the real command line win.
+    /// Repeated default flags are all applied. Positionals and trailing arguments in `line`
+    /// are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The default flags, e.g. `"--color=auto --jobs=4"`.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or the `TokenizeError` if `line` has an unterminated quote.
+    pub fn merge_defaults_from_str(&mut self, line: &str) -> Result<(), TokenizeError> {
+        let words = shell_split(line)?;
+        let defaults = Command::parse_with_config(
+            std::iter::once(String::new()).chain(words),
+            &ParseConfig::default(),
+        );
+        let missing: Vec<Arg> = defaults
+            .opts
+            .into_iter()
+            .filter(|d| d.opt_type != OptionType::Simple)
+            .filter(|d| !self.opts.iter().any(|o| o.opt_str == d.opt_str))
+            .collect();
+        self.opts.extend(missing);
+        Ok(())
+    }
+
+    /// Parses every value of the named long option into `T`.
+    ///
+    /// The first occurrence of the flag is used, and parsing stops at the first value that
+    /// fails. For `--ports=80,443,8080`, `get_parsed_vec::<u16>("--ports")` returns
+    /// `Some(Ok(vec![80, 443, 8080]))`. `name` may be given with or without leading hyphens.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to look up (e.g., `"--ports"` or `"ports"`).
+    ///
+    /// # Returns
+    ///
+    /// `None` if the flag is absent, otherwise the parsed values or the first parse error.
+    pub fn get_parsed_vec<T: FromStr>(
+        &self,
+        name: &str,
+    ) -> std::option::Option<Result<Vec<T>, T::Err>> {
+        self.opts
+            .iter()
+            .find(|o| o.opt_type == OptionType::LongOpt && o.matches(name))
+            .map(|o| o.opt_values.iter().map(|v| v.parse()).collect())
+    }
+
+    /// Parses the first value of the named option into `T`.
+    ///
+    /// For `--port=8080`, `get_parsed::<u16>("--port")` returns `Some(Ok(8080))`. Only the
+    /// first occurrence of the flag is used, like [`Command::get_values`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to look up, with or without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the flag is absent or has no values, otherwise the parse result.
+    pub fn get_parsed<T: FromStr>(&self, name: &str) -> std::option::Option<Result<T, T::Err>> {
+        self.get_values(name)?.first().map(|v| v.parse())
+    }
+
+    /// Parses the values of every occurrence of the named option into `T`.
+    ///
+    /// Unlike [`Command::get_parsed_vec`], which reads only the first occurrence, this
+    /// collects across repetitions: `--ids=1,2 --ids=3` gives `Some(Ok(vec![1, 2, 3]))`.
+    /// Parsing stops at the first value that fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to look up, with or without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the flag is absent, otherwise the parsed values in parse order or the
+    /// first parse error.
+    pub fn get_parsed_all<T: FromStr>(
+        &self,
+        name: &str,
+    ) -> std::option::Option<Result<Vec<T>, T::Err>> {
+        self.has_flag(name).then(|| {
+            self.opts
+                .iter()
+                .filter(|o| o.matches(name))
+                .flat_map(|o| &o.opt_values)
+                .map(|v| v.parse())
+                .collect()
+        })
+    }
+
+    /// Keeps only the values of the named long option for which `f` returns `true`.
+    ///
+    /// Every occurrence of the flag is filtered, visiting values in parse order, so a
+    /// stateful closure can drop duplicates across occurrences. An occurrence left without
+    /// values stays in `opts` as a valueless flag; remove it separately if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag whose values are filtered, with or without leading hyphens.
+    /// * `f` - The predicate deciding which values to keep.
+    pub fn retain_values<F: FnMut(&str) -> bool>(&mut self, name: &str, mut f: F) {
+        for opt in self
+            .opts
+            .iter_mut()
+            .filter(|o| o.opt_type == OptionType::LongOpt && o.matches(name))
+        {
+            opt.opt_values.retain(|v| f(v));
+        }
+    }
+
+    /// Reports suspicious but accepted input, without changing the parse result.
+    ///
+    /// Currently this flags short option bundles containing `-` past the leading hyphen,
+    /// such as `-x-y` (parsed as `-x`, `--`, `-y`). Bundled short options are normally
+    /// letters, so this is almost always a typo.
+    ///
+    /// # Returns
+    ///
+    /// One human-readable warning per finding, in parse order. Empty if nothing was found.
+    pub fn lint(&self) -> Vec<String> {
+        self.opts
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.opt_type == OptionType::ShortOpt && o.opt_str == "--")
+            .map(|(i, _)| {
+                format!(
+                    "option {}: short option bundle contains `-`; bundled short options are normally letters",
+                    i + 1
+                )
+            })
+            .collect()
+    }
+
+    /// Checks whether the named long option was given with at least one non-empty value.
+    ///
+    /// This distinguishes `--config=file` (`true`) from a bare `--config` or an absent flag
+    /// (`false`). Any occurrence of the flag may supply the value.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to look up, with or without leading hyphens.
+    pub fn has_flag_with_value(&self, name: &str) -> bool {
+        self.opts
+            .iter()
+            .filter(|o| o.opt_type == OptionType::LongOpt && o.matches(name))
+            .any(|o| o.opt_values.iter().any(|v| !v.is_empty()))
+    }
+
+    /// Returns the arguments that followed the `--` separator.
+    ///
+    /// This is the `args` field, kept verbatim whether or not they look like options. Use
+    /// [`Command::into_args`] to take them without cloning.
+    ///
+    /// # Returns
+    ///
+    /// The arguments after `--`, or an empty slice if there was no separator.
+    pub fn args_after_double_dash(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Consumes the `Command` and returns the arguments that followed the `--` separator.
+    ///
+    /// # Returns
+    ///
+    /// The `args` field by value; everything else is dropped.
+    pub fn into_args(self) -> Vec<String> {
+        self.args
+    }
+
+    /// Returns the options and simple arguments parsed before the `--` separator.
+    ///
+    /// # Returns
+    ///
+    /// The `opts` field as a slice, in parse order.
+    pub fn opts_slice(&self) -> &[Arg] {
+        &self.opts
+    }
+
+    /// Consumes the `Command` and returns its command name, options, and trailing arguments.
+    ///
+    /// `arg_groups` and `annotations` are dropped.
+    ///
+    /// # Returns
+    ///
+    /// A `(cmd_name, opts, args)` tuple holding the fields by value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::{Command, ParseConfig};
+    ///
+    /// let command = Command::parse_with_config(["prog", "-v", "--", "a"], &ParseConfig::default());
+    /// let (cmd_name, opts, args) = command.into_parts();
+    /// assert_eq!(cmd_name, "prog");
+    /// assert_eq!(opts[0].opt_str, "-v");
+    /// assert_eq!(args, ["a"]);
+    /// ```
+    pub fn into_parts(self) -> (String, Vec<Arg>, Vec<String>) {
+        (self.cmd_name, self.opts, self.args)
+    }
+
+    /// Iterates over the values of every occurrence of the named long option, in parse order.
+    fn all_values<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a String> {
+        self.opts
+            .iter()
+            .filter(move |o| o.opt_type == OptionType::LongOpt && o.matches(name))
+            .flat_map(|o| &o.opt_values)
+    }
+
+    /// Compares the values of the named long option between two parse results.
+    ///
+    /// Values from every occurrence of the flag are considered, so this explains how an
+    /// accumulating list such as `--include` changed between two invocations.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The `Command` to compare against.
+    /// * `name` - The flag to compare, with or without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the values found only in `self` (removed) and only in `other` (added),
+    /// each in parse order.
+    pub fn value_diff<'a>(
+        &'a self,
+        other: &'a Command,
+        name: &str,
+    ) -> (Vec<&'a str>, Vec<&'a str>) {
+        let ours: Vec<&str> = self.all_values(name).map(String::as_str).collect();
+        let theirs: Vec<&str> = other.all_values(name).map(String::as_str).collect();
+        let only_ours = ours
+            .iter()
+            .filter(|v| !theirs.contains(v))
+            .copied()
+            .collect();
+        let only_theirs = theirs
+            .iter()
+            .filter(|v| !ours.contains(v))
+            .copied()
+            .collect();
+        (only_ours, only_theirs)
+    }
+
+    /// Reconstructs just the flags, for logging which switches were set.
+    ///
+    /// Short and long options are written in parse order, separated by spaces. Options with
+    /// values are written as `flag=v1,v2` (or `flag+=v1,v2` for append syntax). Bundled
+    /// short options are written separately. `Simple` arguments and trailing `args` are
+    /// omitted, and the output is never colored.
+    ///
+    /// # Returns
+    ///
+    /// A `String` with the flags, or an empty string if there are none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::{Command, ParseConfig};
+    ///
+    /// let command = Command::parse_with_config(
+    ///     ["prog", "-vq", "in.txt", "--data=a,b", "--force", "--", "-x"],
+    ///     &ParseConfig::default(),
+    /// );
+    /// assert_eq!(command.flags_str(), "-v -q --data=a,b --force");
+    /// ```
+    pub fn flags_str(&self) -> String {
+        self.opts
+            .iter()
+            .filter(|o| o.opt_type != OptionType::Simple)
+            .map(|o| {
+                if o.opt_values.is_empty() {
+                    o.opt_str.clone()
+                } else {
+                    let sign = if o.append { "+=" } else { "=" };
+                    format!("{}{}{}", o.opt_str, sign, o.opt_values.join(","))
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Finds the first occurrence of the named flag together with its position in `opts`.
+    ///
+    /// The position indexes `opts` (not the original argument list), so it can be used to
+    /// modify or remove the entry later.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to look up, with or without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// The index and a reference to the first matching option, or `None` if absent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::{Command, ParseConfig};
+    ///
+    /// let command = Command::parse_with_config(
+    ///     ["prog", "file.txt", "-v", "--mode=fast"],
+    ///     &ParseConfig::default(),
+    /// );
+    /// let (index, opt) = command.opt_with_index("--mode").unwrap();
+    /// assert_eq!(index, 2);
+    /// assert_eq!(opt.opt_values, ["fast"]);
+    /// assert!(command.opt_with_index("--missing").is_none());
+    /// ```
+    pub fn opt_with_index(&self, name: &str) -> std::option::Option<(usize, &Arg)> {
+        self.opts.iter().enumerate().find(|(_, o)| o.matches(name))
+    }
+
+    /// Collects `key=value` pairs carried by long options, e.g. from `--set:key=value`.
+    ///
+    /// Every value of a long option that contains `=` is split on its first `=`. This covers
+    /// the payloads produced by [`ParseConfig::structured_options`] as well as values such as
+    /// `--define=KEY=VALUE` written without the structured syntax.
+    ///
+    /// # Returns
+    ///
+    /// `(flag, key, value)` tuples in parse order, e.g. `("--set", "database.host", "localhost")`.
+    pub fn structured_sets(&self) -> Vec<(&str, &str, &str)> {
+        self.opts
+            .iter()
+            .filter(|o| o.opt_type == OptionType::LongOpt)
+            .flat_map(|o| {
+                o.opt_values
+                    .iter()
+                    .filter_map(|v| v.split_once('='))
+                    .map(|(key, value)| (o.opt_str.as_str(), key, value))
+            })
+            .collect()
+    }
+
+    /// Returns the parsed options as uncolored table rows, one per entry in `opts`.
+    ///
+    /// This exposes the data behind the `Display` output for consumers that render their
+    /// own tables. Positionals are included with the type `"Simple"`.
+    ///
+    /// # Returns
+    ///
+    /// `(opt_str, type_name, values)` tuples, where `type_name` is `"Simple"`,
+    /// `"Short Option"`, or `"Long Option"` and `values` is joined with `", "` (empty if none).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::{Command, ParseConfig};
+    ///
+    /// let command = Command::parse_with_config(
+    ///     ["prog", "-v", "--data=a,b", "file.txt"],
+    ///     &ParseConfig::default(),
+    /// );
+    /// let rows = command.rows();
+    /// assert_eq!(rows[0], ("-v".into(), "Short Option".into(), "".into()));
+    /// assert_eq!(rows[1], ("--data".into(), "Long Option".into(), "a, b".into()));
+    /// assert_eq!(rows[2], ("file.txt".into(), "Simple".into(), "".into()));
+    /// ```
+    pub fn rows(&self) -> Vec<(String, String, String)> {
+        self.opts
+            .iter()
+            .map(|o| {
+                (
+                    o.opt_str.clone(),
+                    o.opt_type.label().to_string(),
+                    o.opt_values.join(", "),
+                )
+            })
+            .collect()
+    }
+
+    /// Replaces every value equal to `from` with `to` in the named long option.
+    ///
+    /// All occurrences of the flag are updated, which helps migrate deprecated values
+    /// (e.g., `--mode=legacy` to `--mode=compat`) before processing. Other values and
+    /// other flags are left untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `flag` - The flag whose values are rewritten, with or without leading hyphens.
+    /// * `from` - The value to replace.
+    /// * `to` - The replacement value.
+    pub fn rename_value(&mut self, flag: &str, from: &str, to: &str) {
+        for opt in self
+            .opts
+            .iter_mut()
+            .filter(|o| o.opt_type == OptionType::LongOpt && o.matches(flag))
+        {
+            for value in opt.opt_values.iter_mut().filter(|v| *v == from) {
+                *value = to.to_string();
+            }
+        }
+    }
+
+    /// Removes all values from every occurrence of the named long option.
+    ///
+    /// The occurrences stay in `opts` as bare flags, so the flag's presence is still
+    /// honored while its values (e.g., a deprecated value) are discarded. An `append`
+    /// marker is cleared along with the values.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The long option to clear, with or without leading hyphens.
+    pub fn clear_values(&mut self, name: &str) {
+        for opt in self
+            .opts
+            .iter_mut()
+            .filter(|o| o.opt_type == OptionType::LongOpt && o.matches(name))
+        {
+            opt.opt_values.clear();
+            opt.append = false;
+        }
+    }
+
+    /// Returns the positions in `opts` of every option of the given type.
+    ///
+    /// The positions index `opts`, in ascending order, so they can be used to insert or
+    /// remove entries by slot (e.g., all positional arguments via `OptionType::Simple`).
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - The option type to locate.
+    ///
+    /// # Returns
+    ///
+    /// The indices of all matching options, or an empty vector if there are none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::{Command, OptionType, ParseConfig};
+    ///
+    /// let command = Command::parse_with_config(
+    ///     ["prog", "a.txt", "-v", "b.txt", "--mode=fast"],
+    ///     &ParseConfig::default(),
+    /// );
+    /// assert_eq!(command.positions_of_type(OptionType::Simple), [0, 2]);
+    /// assert_eq!(command.positions_of_type(OptionType::LongOpt), [3]);
+    /// ```
+    pub fn positions_of_type(&self, t: OptionType) -> Vec<usize> {
+        self.opts
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.opt_type == t)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Builds a warning for every deprecated flag present in `opts`.
+    ///
+    /// Each flag produces at most one message, such as ``"`--old` is deprecated; use
+    /// `--new`"``, no matter how often it occurs. Messages follow the order of `deprecated`,
+    /// and flags are shown as written in the table.
+    ///
+    /// # Arguments
+    ///
+    /// * `deprecated` - `(old_flag, replacement)` pairs; `old_flag` may be given with or
+    ///   without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// One message per deprecated flag present, or an empty vector if none are present.
+    pub fn check_deprecated(&self, deprecated: &[(&str, &str)]) -> Vec<String> {
+        deprecated
+            .iter()
+            .filter(|(old, _)| self.opts.iter().any(|o| o.matches(old)))
+            .map(|(old, new)| format!("`{}` is deprecated; use `{}`", old, new))
+            .collect()
+    }
+
+    /// Lazily yields `(opt_str, value)` for every value of every option, in parse order.
+    ///
+    /// Options without values yield nothing, so the iterator composes directly with
+    /// adapters such as `filter` and `collect`.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over flag/value pairs borrowed from `opts`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::{Command, ParseConfig};
+    ///
+    /// let command = Command::parse_with_config(
+    ///     ["prog", "--data=a,b", "-v", "file.txt", "--mode=fast"],
+    ///     &ParseConfig::default(),
+    /// );
+    /// let pairs = command.pairs().collect::<Vec<_>>();
+    /// assert_eq!(pairs, [("--data", "a"), ("--data", "b"), ("--mode", "fast")]);
+    /// ```
+    pub fn pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.opts.iter().flat_map(|o| {
+            o.opt_values
+                .iter()
+                .map(move |value| (o.opt_str.as_str(), value.as_str()))
+        })
+    }
+
+    /// Keeps only the first `max` entries of `opts`, discarding the rest.
+    ///
+    /// This is a post-parse trim for tools that only care about leading flags or want a
+    /// defensive limit; it does not change how arguments are classified, and trailing
+    /// `args` are left untouched. Has no effect if `opts` already has `max` or fewer entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - The number of options to keep.
+    pub fn truncate_opts(&mut self, max: usize) {
+        self.opts.truncate(max);
+    }
+
+    /// Reads a feature toggled by a lowercase/uppercase short option pair, such as `-e`/`-E`.
+    ///
+    /// The lowercase form enables the feature and the uppercase form disables it. When
+    /// both appear, the last occurrence wins. Bundles count, so `-xE` disables.
+    ///
+    /// # Arguments
+    ///
+    /// * `lower` - The lowercase option character (e.g., `'e'`).
+    ///
+    /// # Returns
+    ///
+    /// `Some(true)` if `-lower` occurs last, `Some(false)` if its ASCII uppercase form occurs
+    /// last, or `None` if neither is present. Also `None` if `lower` has no distinct ASCII
+    /// uppercase form (e.g., `'E'` or `'1'`), since there is no pair to toggle between.
+    pub fn short_toggle(&self, lower: char) -> std::option::Option<bool> {
+        if lower == lower.to_ascii_uppercase() {
+            return None;
+        }
+        let enable = format!("-{}", lower);
+        let disable = format!("-{}", lower.to_ascii_uppercase());
+        self.opts
+            .iter()
+            .rev()
+            .filter(|o| o.opt_type == OptionType::ShortOpt)
+            .find_map(|o| {
+                if o.opt_str == enable {
+                    Some(true)
+                } else if o.opt_str == disable {
+                    Some(false)
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Renders long options as make-style variable assignments (e.g., `OUT_DIR=build`).
+    ///
+    /// Each long option occurrence becomes one `NAME=value` string:
+    ///
+    /// 1. The leading hyphens are dropped, the name is uppercased, and remaining hyphens
+    ///    become underscores, so `--out-dir` becomes `OUT_DIR`.
+    /// 2. Values are joined with a single space, so `--cflags=-O2,-g` becomes `CFLAGS=-O2 -g`.
+    /// 3. A valueless flag renders as `NAME=1`, so `--verbose` becomes `VERBOSE=1`.
+    ///
+    /// Short options and simple arguments are skipped. The strings are not quoted; pass
+    /// each one as a single argument to `make`.
+    ///
+    /// # Returns
+    ///
+    /// One assignment per long option, in parse order.
+    pub fn to_make_assignments(&self) -> Vec<String> {
+        self.opts
+            .iter()
+            .filter(|o| o.opt_type == OptionType::LongOpt)
+            .map(|o| {
+                let name = o
+                    .opt_str
+                    .trim_start_matches('-')
+                    .to_uppercase()
+                    .replace('-', "_");
+                let value = if o.opt_values.is_empty() {
+                    "1".to_string()
+                } else {
+                    o.opt_values.join(" ")
+                };
+                format!("{}={}", name, value)
+            })
+            .collect()
+    }
+
+    /// Returns the widths of the longest `opt_str` and the longest single value in `opts`.
+    ///
+    /// Widths are counted in characters (`chars().count()`), not bytes, so multibyte text
+    /// is measured correctly for aligning output columns.
+    ///
+    /// # Returns
+    ///
+    /// A `(flag_width, value_width)` pair; either is `0` when there is nothing to measure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::{Command, ParseConfig};
+    ///
+    /// let command = Command::parse_with_config(
+    ///     ["prog", "--name=日本語", "-v", "--city=Tokyo"],
+    ///     &ParseConfig::default(),
+    /// );
+    /// assert_eq!(command.max_widths(), (6, 5));
+    /// ```
+    pub fn max_widths(&self) -> (usize, usize) {
+        let flag_width = self
+            .opts
+            .iter()
+            .map(|o| o.opt_str.chars().count())
+            .max()
+            .unwrap_or(0);
+        let value_width = self
+            .opts
+            .iter()
+            .flat_map(|o| &o.opt_values)
+            .map(|v| v.chars().count())
+            .max()
+            .unwrap_or(0);
+        (flag_width, value_width)
+    }
+
+    /// Checks whether the command line asks only for help or version information.
+    ///
+    /// Returns `true` when at least one of `-h`, `--help`, `-V`, or `--version` is present
+    /// and nothing else was supplied: any other option, any simple argument, or any
+    /// argument after `--` makes it `false`. A tool can use this to print usage and exit
+    /// before validating the rest of its input.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the only options are help/version requests, `false` otherwise.
+    pub fn is_help_only(&self) -> bool {
+        !self.opts.is_empty()
+            && self.args.is_empty()
+            && self.opts.iter().all(|o| o.is_help() || o.is_version())
+    }
+
+    /// Checks whether help was requested with `-h` or `--help` anywhere in `opts`.
+    ///
+    /// Only these exact flags count, so `--helpme` does not, while a bundle such as `-hv`
+    /// does because it contains `-h`. Arguments after `--` are ignored. Unlike
+    /// [`Command::is_help_only`], other options may be present.
+    pub fn wants_help(&self) -> bool {
+        self.opts.iter().any(Arg::is_help)
+    }
+
+    /// Checks whether the version was requested with `-V` or `--version` anywhere in `opts`.
+    ///
+    /// Only these exact flags count; note that lowercase `-v` is commonly `--verbose` and
+    /// does not match. Arguments after `--` are ignored.
+    pub fn wants_version(&self) -> bool {
+        self.opts.iter().any(Arg::is_version)
+    }
+
+    /// Resolves a single-valued flag that may have been given several times.
+    ///
+    /// The values of every occurrence are considered in parse order, so both
+    /// `--mode=a --mode=b` and `--mode=a,b` count as two values. `policy` decides which one
+    /// wins; with [`ResolvePolicy::ErrorOnConflict`], differing values yield `None` here, and
+    /// [`Command::try_resolve_single`] reports them as an error instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to resolve, with or without leading hyphens.
+    /// * `policy` - How to choose among several values.
+    ///
+    /// # Returns
+    ///
+    /// The chosen value, or `None` if the flag has no values (or they conflict under
+    /// `ErrorOnConflict`).
+    pub fn resolve_single(&self, name: &str, policy: ResolvePolicy) -> std::option::Option<&str> {
+        self.try_resolve_single(name, policy).ok().flatten()
+    }
+
+    /// Resolves a single-valued flag like [`Command::resolve_single`], reporting conflicts.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to resolve, with or without leading hyphens.
+    /// * `policy` - How to choose among several values.
+    ///
+    /// # Returns
+    ///
+    /// The chosen value (`None` if the flag has no values), or
+    /// `ParseError::ConflictingValues` if `policy` is `ErrorOnConflict` and two values differ.
+    pub fn try_resolve_single(
+        &self,
+        name: &str,
+        policy: ResolvePolicy,
+    ) -> Result<std::option::Option<&str>, ParseError> {
+        let mut values = self
+            .opts
+            .iter()
+            .filter(|o| o.matches(name))
+            .flat_map(|o| &o.opt_values)
+            .map(String::as_str);
+        match policy {
+            ResolvePolicy::First => Ok(values.next()),
+            ResolvePolicy::Last => Ok(values.next_back()),
+            ResolvePolicy::ErrorOnConflict => {
+                let first = values.next();
+                match (first, values.find(|v| Some(*v) != first)) {
+                    (Some(first), Some(second)) => Err(ParseError::ConflictingValues {
+                        flag: name.to_string(),
+                        first: first.to_string(),
+                        second: second.to_string(),
+                    }),
+                    _ => Ok(first),
+                }
+            }
+        }
+    }
+
+    /// Returns a [`Matches`] view mirroring clap's classic query API.
+    ///
+    /// # Returns
+    ///
+    /// A `Matches` borrowing this command.
+    pub fn matches(&self) -> Matches<'_> {
+        Matches { command: self }
+    }
+
+    /// Returns the values of the named long option as owned lines, one value per line.
+    ///
+    /// Values from every occurrence are included in parse order, ready to be written to a
+    /// file or fed to a line-oriented tool (e.g., an `--exclude` list).
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The long option to read, with or without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// One string per value, or an empty vector if the option is absent or valueless.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::{Command, ParseConfig};
+    ///
+    /// let command = Command::parse_with_config(
+    ///     ["prog", "--exclude=target,.git", "-v", "--exclude=*.tmp"],
+    ///     &ParseConfig::default(),
+    /// );
+    /// assert_eq!(command.value_lines("exclude").join("\n"), "target\n.git\n*.tmp");
+    /// assert!(command.value_lines("--missing").is_empty());
+    /// ```
+    pub fn value_lines(&self, name: &str) -> Vec<String> {
+        self.opts
+            .iter()
+            .filter(|o| o.opt_type == OptionType::LongOpt && o.matches(name))
+            .flat_map(|o| o.opt_values.iter().cloned())
+            .collect()
+    }
+
+    /// Parses arguments like [`Command::parse_with_config`] after expanding response files.
+    ///
+    /// Before `--`, every argument after the command name that starts with `@` is replaced
+    /// by the words of the text it refers to, split with [`shell_split`]:
+    ///
+    /// - `@-` reads the rest of standard input once; a later `@-` finds it exhausted and
+    ///   expands to nothing, as does an empty input.
+    /// - `@path` reads the file at `path`.
+    ///
+    /// The words are spliced in at the position of the `@` token and are expanded the same
+    /// way, so a response file may refer to further response files. A file that refers
+    /// back to itself, directly or through other files, is an error rather than endless
+    /// recursion; the same file may still be used several times side by side. Arguments
+    /// after a `--`, whether on the command line or inside a response file, are kept
+    /// literally. Because `@` tokens are consumed here, they never reach
+    /// [`ParseConfig::annotation_prefix`].
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    /// * `config` - The settings controlling classification.
+    /// * `source` - Where response file and standard input contents are read from.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Command`, `ParseError::ResponseFileNotFound` if a response file does not
+    /// exist, or `ParseError::ResponseFile` if a source cannot be read, its contents cannot
+    /// be tokenized, or it refers back to itself.
+    pub fn parse_with_response_files<I, S, R>(
+        args: I,
+        config: &ParseConfig,
+        source: &mut R,
+    ) -> Result<Command, ParseError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+        R: ResponseSource,
+    {
+        let mut args_iter = args.into_iter().map(Into::into);
+        let mut expanded: Vec<String> = args_iter.next().into_iter().collect();
+        expand_response_files(args_iter, config, source, &mut Vec::new(), &mut expanded)?;
+        Ok(Command::parse_with_config(expanded, config))
+    }
+
+    /// Parses arguments like [`Command::parse_from`] after expanding response files.
+    ///
+    /// This is [`Command::parse_with_response_files`] with the default configuration,
+    /// reading `@path` from the file system and `@-` from standard input, so
+    /// `prog @args.txt` behaves as if the words of `args.txt` had been typed in its place.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Command`, or the errors described on
+    /// [`Command::parse_with_response_files`].
+    pub fn parse_from_with_response_files<I, S>(args: I) -> Result<Command, ParseError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Command::parse_with_response_files(args, &ParseConfig::default(), &mut SystemResponseSource)
+    }
+
+    /// Checks whether the named flag was passed.
+    ///
+    /// `name` may be written as on the command line (`"-v"`, `"--verbose"`) or as the bare
+    /// letter or word (`"v"`, `"verbose"`). Bundled short options count individually, so
+    /// `-xvz` makes `has_flag("v")` true. Simple arguments never match.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to look for, with or without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// `true` if any option in `opts` matches `name`.
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.opts.iter().any(|o| o.matches(name))
+    }
+
+    /// Returns the values of the first occurrence of the named option.
+    ///
+    /// If the option appears several times (e.g., `--data=a --data=b`), only the first
+    /// occurrence is considered; later occurrences are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The option to look up, with or without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// `Some` with the values (an empty slice for a valueless flag), or `None` if the
+    /// option was not passed.
+    pub fn get_values(&self, name: &str) -> std::option::Option<&[String]> {
+        self.opts
+            .iter()
+            .find(|o| o.matches(name))
+            .map(|o| o.opt_values.as_slice())
+    }
+
+    /// Returns the values of every occurrence of the named option, concatenated in order.
+    ///
+    /// This suits flags meant to accumulate, such as include paths or defines:
+    /// `--D=X --D=Y,Z` gives `["X", "Y", "Z"]`. Use [`Command::get_values`] when only the
+    /// first occurrence should count.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The option to look up, with or without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// The values in parse order, or an empty vector if the option was not passed or has
+    /// no values.
+    pub fn get_all_values(&self, name: &str) -> Vec<&String> {
+        self.all_values(name).collect()
+    }
+
+    /// Checks whether the named option is present, ignoring ASCII case.
+    ///
+    /// This is the case-insensitive form of [`Command::has_flag`]: `has_flag_ci("verbose")`
+    /// finds `--Verbose` and `--VERBOSE`. Only ASCII letters are folded, so the result
+    /// does not depend on the locale. Other lookups remain case-sensitive.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to look for, with or without leading hyphens.
+    pub fn has_flag_ci(&self, name: &str) -> bool {
+        self.opts.iter().any(|o| o.matches_ci(name))
+    }
+
+    /// Returns the values of the first occurrence of the named option, ignoring ASCII case.
+    ///
+    /// This is the case-insensitive form of [`Command::get_values`]: `get_values_ci("DATA")`
+    /// finds an option stored as `--data`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The option to look up, with or without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// `Some` with the values, or `None` if no option matches.
+    pub fn get_values_ci(&self, name: &str) -> std::option::Option<&[String]> {
+        self.opts
+            .iter()
+            .find(|o| o.matches_ci(name))
+            .map(|o| o.opt_values.as_slice())
+    }
+
+    /// Counts how many times the named flag occurs in `opts`.
+    ///
+    /// Bundled and separate forms count the same, so `-vvv` and `-v -v -v` both give 3,
+    /// which makes this suitable for verbosity levels: `let level = cmd.count_flag("v");`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to count, short or long, with or without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// The number of occurrences, or `0` if the flag is absent.
+    pub fn count_flag(&self, name: &str) -> usize {
+        self.opts.iter().filter(|o| o.matches(name)).count()
+    }
+
+    /// Iterates over the long options in `opts` (e.g., `--data=x`), in parse order.
+    pub fn long_opts(&self) -> impl Iterator<Item = &Arg> {
+        self.opts_of_type(OptionType::LongOpt)
+    }
+
+    /// Iterates over the short options in `opts` (e.g., each of `-a`, `-b` from `-ab`), in
+    /// parse order.
+    pub fn short_opts(&self) -> impl Iterator<Item = &Arg> {
+        self.opts_of_type(OptionType::ShortOpt)
+    }
+
+    /// Iterates over the simple arguments before `--` (e.g., `file.txt`), in parse order.
+    ///
+    /// Arguments after `--` are in `args` and are not included.
+    pub fn simple_args(&self) -> impl Iterator<Item = &Arg> {
+        self.opts_of_type(OptionType::Simple)
+    }
+
+    /// Iterates over the options in `opts` of the given type.
+    fn opts_of_type(&self, t: OptionType) -> impl Iterator<Item = &Arg> {
+        self.opts.iter().filter(move |o| o.opt_type == t)
+    }
+
+    /// Parses arguments like [`Command::parse_from`], rejecting malformed long options.
+    ///
+    /// This is [`Command::try_parse_with_config`] with the default configuration, so `--=foo`
+    /// yields `ParseError::EmptyLongOptionName` and `---verbose` yields
+    /// `ParseError::MalformedOption` instead of an `Arg` with a broken name. [`get`] keeps
+    /// its infallible behavior.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Command`, or the first `ParseError` found.
+    pub fn try_parse_from<I, S>(args: I) -> Result<Command, ParseError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Command::try_parse_with_config(args, &ParseConfig::default())
+    }
+
+    /// Parses arguments like [`Command::try_parse_from`], requiring POSIX-strict ordering.
+    ///
+    /// All options must precede the positional arguments: once a `Simple` argument has been
+    /// seen, any later option before `--` is an error. Arguments after `--` are never
+    /// options, so `program file -- -v` is accepted. The permissive parsers such as
+    /// [`get`] keep allowing options and positionals to interleave.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Command`, `ParseError::OptionAfterPositional` holding the first
+    /// misplaced option as written, or any error from [`Command::try_parse_from`].
+    pub fn parse_strict<I, S>(args: I) -> Result<Command, ParseError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let args: Vec<String> = args.into_iter().map(Into::into).collect();
+        let command = Command::try_parse_from(args.iter().cloned())?;
+        let misplaced = command
+            .opts
+            .iter()
+            .skip_while(|o| o.opt_type != OptionType::Simple)
+            .find(|o| o.opt_type != OptionType::Simple);
+        match misplaced {
+            Some(opt) => Err(ParseError::OptionAfterPositional(
+                args[opt.origin_index].clone(),
+            )),
+            None => Ok(command),
+        }
+    }
+
+    /// Renders the parsed command as a JSON object, without any serialization dependency.
+    ///
+    /// The object has the fields `cmd_name`, `opts` (an array of objects with `type`, `str`,
+    /// and `values`), and `args`. `type` is `"simple"`, `"short_opt"`, `"long_opt"`, or
+    /// `"plus_opt"`, as with the `serde` feature. Strings are escaped as JSON requires, so the output can be
+    /// piped to tools such as `jq`.
+    ///
+    /// # Returns
+    ///
+    /// A single-line JSON document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::Command;
+    ///
+    /// let command = Command::parse_from(["prog", "-v", "--", "x"]);
+    /// assert_eq!(
+    ///     command.to_json(),
+    ///     r#"{"cmd_name":"prog","opts":[{"type":"short_opt","str":"-v","values":[]}],"args":["x"]}"#
+    /// );
+    /// ```
+    pub fn to_json(&self) -> String {
+        let opts: Vec<String> = self
+            .opts
+            .iter()
+            .map(|o| {
+                let opt_type = match o.opt_type {
+                    OptionType::Simple => "simple",
+                    OptionType::ShortOpt => "short_opt",
+                    OptionType::LongOpt => "long_opt",
+                    OptionType::PlusOpt => "plus_opt",
+                };
+                format!(
+                    r#"{{"type":"{}","str":{},"values":{}}}"#,
+                    opt_type,
+                    json_string(&o.opt_str),
+                    json_array(&o.opt_values)
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"cmd_name":{},"opts":[{}],"args":{}}}"#,
+            json_string(&self.cmd_name),
+            opts.join(","),
+            json_array(&self.args)
+        )
+    }
+
+    /// Rebuilds a shell-ready command line from the parsed result.
+    ///
+    /// This is the inverse of parsing with the default [`ParseConfig`]: long options get
+    /// their values back after `=` (or `+=`), joined with commas, short options are emitted
+    /// one per word (with `=` before any values), and `--` is added before `args` when
+    /// there are any. A `-` short option, as parsed from `-x-`, is joined to the short
+    /// option before it, since on its own it would read as the separator. Values that would otherwise split or vanish, such as ones containing
+    /// commas, are wrapped in double quotes, and every word is quoted for a POSIX shell
+    /// where needed, so [`shell_split`] followed by [`Command::parse_from`] gives back the
+    /// same `Command`, except that `origin_index` follows the rebuilt words. `arg_groups`
+    /// and `annotations` are not included.
+    ///
+    /// # Returns
+    ///
+    /// The command line as a single string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::Command;
+    ///
+    /// let command = Command::parse_from(["prog", "-ab", "--msg=hello world", "--", "x"]);
+    /// assert_eq!(command.to_command_line(), r#"prog -a -b "--msg=hello world" -- x"#);
+    /// ```
+    pub fn to_command_line(&self) -> String {
+        let mut words = vec![self.cmd_name.clone()];
+        // Whether the last word is a short option without values, which `-` can join.
+        let mut bundle_open = false;
+        for opt in &self.opts {
+            let word = if opt.opt_values.is_empty() || opt.opt_type == OptionType::Simple {
+                opt.opt_str.clone()
+            } else {
+                let values: Vec<String> = opt.opt_values.iter().map(|v| list_value(v)).collect();
+                let marker = if opt.append { "+=" } else { "=" };
+                format!("{}{}{}", opt.opt_str, marker, values.join(","))
+            };
+            match words.last_mut() {
+                Some(last)
+                    if bundle_open
+                        && opt.opt_type == OptionType::ShortOpt
+                        && opt.opt_str == "--" =>
+                {
+                    last.push_str(&word[1..]);
+                }
+                _ => words.push(word),
+            }
+            bundle_open = opt.opt_type == OptionType::ShortOpt && opt.opt_values.is_empty();
+        }
+        if !self.args.is_empty() {
+            words.push("--".to_string());
+            words.extend(self.args.iter().cloned());
+        }
+        quote_words(words)
+    }
+
+    /// Checks whether any positional argument is the conventional stdin marker `-`.
+    ///
+    /// Both simple arguments in `opts` and arguments after `--` are considered, so
+    /// `program -`, `program file -`, and `program -- -` all return `true`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if [`Command::stdin_positions`] is non-empty.
+    pub fn has_stdin_marker(&self) -> bool {
+        self.positional_strs().any(|arg| arg == "-")
+    }
+
+    /// Returns where the stdin marker `-` occurs among the positional arguments.
+    ///
+    /// Positions count the simple arguments in `opts` first and then the arguments after
+    /// `--`, so for `program file -- -` the result is `[1]`. Options are not counted.
+    ///
+    /// # Returns
+    ///
+    /// The positional indices of every `-`, in ascending order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::Command;
+    ///
+    /// let command = Command::parse_from(["program", "-v", "file", "-", "--", "-"]);
+    /// assert_eq!(command.stdin_positions(), [1, 2]);
+    /// ```
+    pub fn stdin_positions(&self) -> Vec<usize> {
+        self.positional_strs()
+            .enumerate()
+            .filter(|(_, arg)| *arg == "-")
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Iterates over the simple arguments in `opts` followed by the arguments after `--`.
+    fn positional_strs(&self) -> impl Iterator<Item = &str> {
+        self.simple_args()
+            .map(|o| o.opt_str.as_str())
+            .chain(self.args.iter().map(String::as_str))
+    }
+
+    /// Reads a `--name`/`--no-name` boolean pair, such as `--color` and `--no-color`.
+    ///
+    /// When both forms are given, the one that appears last in `opts` wins, so
+    /// `--no-color --color` enables the flag.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The positive flag name, with or without leading hyphens (e.g., `"color"`).
+    ///
+    /// # Returns
+    ///
+    /// `Some(true)` if `--name` wins, `Some(false)` if `--no-name` wins, or `None` if
+    /// neither was passed.
+    pub fn bool_flag(&self, name: &str) -> std::option::Option<bool> {
+        let name = name.trim_start_matches('-');
+        let negated = format!("no-{}", name);
+        self.opts.iter().rev().find_map(|o| {
+            if o.opt_type != OptionType::LongOpt {
+                None
+            } else if o.matches(name) {
+                Some(true)
+            } else if o.matches(&negated) {
+                Some(false)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns a terse, uncolored one-line summary of the parse result, for logging.
+    ///
+    /// The summary names the command and counts short options, long options, simple
+    /// arguments, and arguments after `--`, e.g. `prog [2 short] [1 long] [1 simple] [1 after --]`.
+    /// A `[n plus]` count is added only when plus options were parsed.
+    ///
+    /// # Returns
+    ///
+    /// The summary as a `String`.
+    pub fn summary(&self) -> String {
+        let count = |t| self.opts_of_type(t).count();
+        let mut summary = format!(
+            "{} [{} short] [{} long] [{} simple] [{} after --]",
+            self.cmd_name,
+            count(OptionType::ShortOpt),
+            count(OptionType::LongOpt),
+            count(OptionType::Simple),
+            self.args.len()
+        );
+        let plus = count(OptionType::PlusOpt);
+        if plus > 0 {
+            summary.push_str(&format!(" [{} plus]", plus));
+        }
+        summary
+    }
+
+    /// Returns the first value of the named option, falling back to an environment variable.
+    ///
+    /// The command line wins: if the option was passed with a value, that value is
+    /// returned and `env_var` is not read. Otherwise the variable is read with
+    /// [`env::var`], so an unset or non-UTF-8 variable counts as absent.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The option to look up, with or without leading hyphens.
+    /// * `env_var` - The environment variable to read when the option gives no value.
+    ///
+    /// # Returns
+    ///
+    /// The value, or `None` if neither source provides one.
+    pub fn get_value_or_env(&self, name: &str, env_var: &str) -> std::option::Option<String> {
+        self.get_values(name)
+            .and_then(|values| values.first().cloned())
+            .or_else(|| env::var(env_var).ok())
+    }
+
+    /// Returns the options sorted by type and then by flag, for stable diffs across runs.
+    ///
+    /// Options are ordered by [`OptionType`] (`Simple`, then `ShortOpt`, `LongOpt`, and
+    /// `PlusOpt`) and then by `opt_str`. Entries that compare equal keep their parse order.
+    /// `opts` itself is left untouched.
+    ///
+    /// # Returns
+    ///
+    /// References to every entry of `opts`, in sorted order.
+    pub fn sorted_opts(&self) -> Vec<&Arg> {
+        let mut sorted: Vec<&Arg> = self.opts.iter().collect();
+        sorted.sort_by(|a, b| (&a.opt_type, &a.opt_str).cmp(&(&b.opt_type, &b.opt_str)));
+        sorted
+    }
+
+    /// Checks that every listed flag was passed, to fail fast before doing any work.
+    ///
+    /// Each name is matched like [`Command::has_flag`], so `"-o"`, `"--output"`, and the
+    /// bare `"output"` are all accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `required` - The flags that must be present.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if all are present, otherwise `Err` with the missing names as given, in
+    /// the order of `required`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::Command;
+    ///
+    /// let command = Command::parse_from(["prog", "--input=a.txt"]);
+    /// assert_eq!(
+    ///     command.validate_required(&["input", "--output"]),
+    ///     Err(vec!["--output".to_string()])
+    /// );
+    /// ```
+    pub fn validate_required(&self, required: &[&str]) -> Result<(), Vec<String>> {
+        let missing: Vec<String> = required
+            .iter()
+            .filter(|name| !self.has_flag(name))
+            .map(|name| name.to_string())
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(missing)
+        }
+    }
+
+    /// Groups the short options that came from the same bundle, such as `-abc`.
+    ///
+    /// Consecutive `ShortOpt` entries sharing an [`Arg::origin_index`] form one group, in
+    /// parse order; every other entry of `opts` is a group of its own. This lets error
+    /// messages refer to the token as typed (e.g., "invalid bundle -abc"). Entries not
+    /// produced by parsing (with `origin_index` 0) are never grouped.
+    ///
+    /// # Returns
+    ///
+    /// The groups in order, together covering all of `opts`.
+    pub fn bundles(&self) -> Vec<Vec<&Arg>> {
+        self.opts
+            .chunk_by(|a, b| {
+                a.opt_type == OptionType::ShortOpt
+                    && b.opt_type == OptionType::ShortOpt
+                    && a.origin_index != 0
+                    && a.origin_index == b.origin_index
+            })
+            .map(|group| group.iter().collect())
+            .collect()
+    }
+
+    /// Returns an unordered dictionary view of the options, keyed by `opt_str`.
+    ///
+    /// Each key maps to the option's `opt_values`; a flag without values maps to an empty
+    /// vector. Values of repeated options are concatenated under one key, in parse order.
+    /// Keys are the flags as stored (e.g., `"--data"`, `"-v"`), so `--data` and `-d` stay
+    /// distinct. `Simple` arguments and the arguments after `--` are skipped; read them
+    /// from [`Command::simple_args`] and `args`.
+    ///
+    /// # Returns
+    ///
+    /// A `HashMap` from each flag to its values.
+    pub fn to_map(&self) -> HashMap<String, Vec<String>> {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        for opt in self
+            .opts
+            .iter()
+            .filter(|o| o.opt_type != OptionType::Simple)
+        {
+            map.entry(opt.opt_str.clone())
+                .or_default()
+                .extend(opt.opt_values.iter().cloned());
+        }
+        map
+    }
+
+    /// Parses a single command line string, such as a shebang line or a test fixture.
+    ///
+    /// The line is split into words like [`shell_split`] does (whitespace runs collapse,
+    /// single and double quotes group text, `""` is an empty word, backslashes escape, and
+    /// a trailing backslash stays literal), and the words are then parsed like
+    /// [`Command::parse_from`], the first word being the command name. An unterminated
+    /// quote is not an error here: the quoted text runs to the end of the line. Call
+    /// `shell_split` first to reject such input instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The command line to parse.
+    ///
+    /// # Returns
+    ///
+    /// A `Command` containing the parsed command name, options, and trailing arguments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::Command;
+    ///
+    /// let command = Command::parse_from_line(r#"prog -v "a b" --data=x"#);
+    /// assert_eq!(command.cmd_name, "prog");
+    /// assert_eq!(command.opts[1].opt_str, "a b");
+    /// ```
+    pub fn parse_from_line(line: &str) -> Command {
+        let words = split_words(line, false).unwrap_or_default();
+        Command::parse_from(words)
+    }
+}
+
+/// Finds the candidate closest to a mistyped flag, for "did you mean" hints.
+///
+/// Leading hyphens are ignored on both sides, so `--verbsoe` is compared with `verbose`.
+/// A candidate qualifies when its edit distance to `unknown` is at most a third of the
+/// longer name (and at least 1); the closest qualifying candidate is returned, with ties
+/// going to the earlier one.
+///
+/// # Arguments
+///
+/// * `unknown` - The flag that was not recognized.
+/// * `candidates` - The known flag names.
+///
+/// # Returns
+///
+/// The closest candidate as given in `candidates`, or `None` if none is close enough.
+///
+/// # Examples
+///
+/// ```
+/// use cmd_arg::cmd_arg::suggest;
+///
+/// assert_eq!(suggest("--verbsoe", &["--verbose", "--version"]), Some("--verbose"));
+/// assert_eq!(suggest("--xyz", &["--verbose"]), None);
+/// ```
+pub fn suggest<'a>(unknown: &str, candidates: &[&'a str]) -> std::option::Option<&'a str> {
+    let unknown = unknown.trim_start_matches('-');
+    candidates
+        .iter()
+        .map(|candidate| {
+            let name = candidate.trim_start_matches('-');
+            (candidate, name, edit_distance(unknown, name))
+        })
+        .filter(|(_, name, distance)| {
+            let longest = unknown.chars().count().max(name.chars().count());
+            *distance <= (longest / 3).max(1)
+        })
+        .min_by_key(|(_, _, distance)| *distance)
+        .map(|(candidate, _, _)| *candidate)
+}
+
+/// Computes the Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Parses the command-line arguments from the environment into a structured `Command`.
+///
+/// The first argument is the command name. Arguments before `--` are parsed as options
+/// or simple arguments and stored in `opts`. Arguments after `--` are stored in `args`.
+///
+/// # Returns
+///
+/// A `Command` struct containing:
+/// - The command name.
+/// - A vector of parsed options/simple arguments before `--`.
+/// - A vector of arguments after `--`.
+///
+/// # Examples
+///
+/// For a command like `program -iv file.txt --data=apple,banana --verbose -- positional1 --pos-flag`:
+/// - `cmd_name` will be `"program"`.
+/// - `opts` will include `-i`, `-v`, `file.txt`, `--data` (with values `["apple", "banana"]`), and `--verbose`.
+/// - `args` will include `["positional1", "--pos-flag"]`.
+pub fn get() -> Command {
+    Command::parse_from(env::args())
+}
+
+/// Parses the command-line arguments like [`get`], reading them from the platform-native source.
+///
+/// On Windows, arguments are read as UTF-16 through `std::env::args_os` and converted
+/// one by one. Unlike [`get`], which panics on an argument that is not valid Unicode,
+/// this converts such arguments lossily: only the unpaired surrogates are replaced with
+/// `U+FFFD`, and all other characters are kept as given. A file name containing an
+/// unpaired surrogate therefore cannot be round-tripped from the resulting `Command`;
+/// read `std::env::args_os` directly if the exact bytes matter.
+///
+/// On other platforms, this is a fallback that reads `std::env::args_os` and replaces
+/// invalid UTF-8 sequences with `U+FFFD`, again instead of panicking.
+///
+/// # Returns
+///
+/// A `Command` parsed with the default configuration.
+pub fn get_wide() -> Command {
+    Command::parse_with_config(
+        env::args_os().map(|arg| native_to_string(&arg)),
+        &ParseConfig::default(),
+    )
+}
+
+/// Converts a platform-native argument to a `String`, replacing unpaired UTF-16 surrogates.
+#[cfg(windows)]
+fn native_to_string(arg: &std::ffi::OsStr) -> String {
+    use std::os::windows::ffi::OsStrExt;
+
+    let wide: Vec<u16> = arg.encode_wide().collect();
+    String::from_utf16_lossy(&wide)
+}
+
+/// Converts a platform-native argument to a `String`, replacing invalid UTF-8 sequences.
+#[cfg(not(windows))]
+fn native_to_string(arg: &std::ffi::OsStr) -> String {
+    arg.to_string_lossy().into_owned()
+}
+
+/// Retrieves the complete command line as a single string, including the command name and all arguments.
+///
+/// Arguments are joined with spaces as they are, so `hello world` cannot be told apart from
+/// two arguments. Prefer [`cmd_str_quoted`] for output meant to be pasted into a shell.
+///
+/// # Returns
+///
+/// A `String` containing all command-line arguments joined by spaces.
+///
+/// # Examples
+///
+/// For a command like `program -v file.txt`, this returns:
+/// ```text
+/// "program -v file.txt"
+/// ```
+pub fn cmd_str() -> String {
+    env::args().collect::<Vec<String>>().join(" ")
+}
+
+/// Retrieves the complete command line as a single string that a POSIX shell splits back
+/// into the same arguments.
+///
+/// Arguments containing whitespace, quotes, or shell metacharacters are wrapped in double
+/// quotes, with embedded `"`, `\`, `$`, and `` ` `` escaped by a backslash, and an empty
+/// argument becomes `""`. Arguments containing `!` are single-quoted instead, so bash does
+/// not expand them as history. Other arguments are kept as they are, so a plain command line
+/// looks the same as with [`cmd_str`]. This is the recommended form for logs and error
+/// messages.
+///
+/// # Returns
+///
+/// A `String` containing all command-line arguments, quoted where needed and joined by
+/// spaces.
+///
+/// # Examples
+///
+/// For a command like `program --msg "a b"`, this returns:
+/// ```text
+/// program --msg "a b"
+/// ```
+pub fn cmd_str_quoted() -> String {
+    quote_words(env::args())
+}
+
+/// Quotes each word with [`shell_quote`] and joins them with spaces.
+fn quote_words<I: IntoIterator<Item = String>>(words: I) -> String {
+    words
+        .into_iter()
+        .map(|word| shell_quote(&word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Describes why [`shell_split`] could not tokenize its input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenizeError {
+    /// A quote (`'` or `"`) was opened but never closed. Holds the quote character.
+    UnterminatedQuote(char),
+}
+
+/// Implements the `Display` trait for `TokenizeError` to provide a human-readable message.
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenizeError::UnterminatedQuote(quote) => {
+                write!(f, "unterminated {} quote", quote)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TokenizeError {}
+
+/// Splits a string into words the way a POSIX shell would, without any expansion.
+///
+/// - Unquoted whitespace separates words; runs of whitespace are collapsed.
+/// - Single quotes preserve everything up to the closing `'` literally.
+/// - Double quotes preserve their contents, except that `\"`, `\\`, `\$`, and `` \` `` are
+///   unescaped.
+/// - Outside quotes, a backslash makes the next character literal. A trailing backslash
+///   is kept as a literal `\`.
+/// - Quoted empty strings (`""` or `''`) produce empty words.
+///
+/// # Arguments
+///
+/// * `input` - The text to split.
+///
+/// # Returns
+///
+/// The words in order, or a `TokenizeError` if a quote is left open.
+///
+/// # Examples
+///
+/// ```
+/// use cmd_arg::cmd_arg::shell_split;
+///
+/// let words = shell_split(r#"prog -v "a b" 'it"s' c\ d"#).unwrap();
+/// assert_eq!(words, ["prog", "-v", "a b", "it\"s", "c d"]);
+/// ```
+pub fn shell_split(input: &str) -> Result<Vec<String>, TokenizeError> {
+    split_words(input, true)
+}
+
+/// Splits `input` as described on [`shell_split`].
+///
+/// # Arguments
+///
+/// * `input` - The text to split.
+/// * `strict` - Whether an unterminated quote is an error. Otherwise the quoted text runs
+///   to the end of the input.
+fn split_words(input: &str, strict: bool) -> Result<Vec<String>, TokenizeError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None if strict => return Err(TokenizeError::UnterminatedQuote('\'')),
+                        None => break,
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None if strict => return Err(TokenizeError::UnterminatedQuote('"')),
+                            None => {
+                                word.push('\\');
+                                break;
+                            }
+                        },
+                        Some(c) => word.push(c),
+                        None if strict => return Err(TokenizeError::UnterminatedQuote('"')),
+                        None => break,
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                word.push(chars.next().unwrap_or('\\'));
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Describes why a fallible parsing function rejected its input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// A flag that is not declared in the [`Schema`]. Holds the flag as written.
+    UnknownOption(String),
+
+    /// A flag carried fewer values than [`ParseConfig::min_values`] requires.
+    TooFewValues {
+        /// The flag as written.
+        flag: String,
+        /// The number of values given.
+        got: usize,
+        /// The minimum number of values required.
+        need: usize,
+    },
+
+    /// A long option was given as `--flag=value` while
+    /// [`ParseConfig::long_values_require_equals`] is disabled. Holds the flag as written,
+    /// without the value.
+    UnexpectedValue(String),
+
+    /// A long option has no name between `--` and its value, as in `--=foo`. Holds the
+    /// argument as written.
+    EmptyLongOptionName(String),
+
+    /// An argument looks like an option but cannot be one, such as `---verbose`. Holds the
+    /// argument as written.
+    MalformedOption(String),
+
+    /// A response file (`@path`) does not exist. Holds the path after `@`.
+    ResponseFileNotFound(String),
+
+    /// A response file (`@path`) or standard input (`@-`) could not be read or tokenized,
+    /// or a response file refers back to itself.
+    ResponseFile {
+        /// The path after `@`, or `-` for standard input.
+        path: String,
+        /// A description of the underlying failure.
+        reason: String,
+    },
+
+    /// A single-valued flag was given differing values under
+    /// [`ResolvePolicy::ErrorOnConflict`].
+    ConflictingValues {
+        /// The flag as requested.
+        flag: String,
+        /// The first value given.
+        first: String,
+        /// The first value that differs from `first`.
+        second: String,
+    },
+
+    /// An option followed a positional argument in [`Command::parse_strict`]. Holds the
+    /// option's argument as written.
+    OptionAfterPositional(String),
+}
+
+/// Implements the `Display` trait for `ParseError` to provide a human-readable message.
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnknownOption(flag) => write!(f, "unknown option `{}`", flag),
+            ParseError::TooFewValues { flag, got, need } => write!(
+                f,
+                "option `{}` needs at least {} value(s) but got {}",
+                flag, need, got
+            ),
+            ParseError::UnexpectedValue(flag) => write!(
+                f,
+                "option `{}` takes its value as a separate argument, not after `=`",
+                flag
+            ),
+            ParseError::EmptyLongOptionName(arg) => {
+                write!(f, "long option `{}` has an empty name", arg)
+            }
+            ParseError::MalformedOption(arg) => write!(f, "malformed option `{}`", arg),
+            ParseError::ResponseFileNotFound(path) => {
+                write!(f, "response file `@{}` not found", path)
+            }
+            ParseError::ResponseFile { path, reason } => {
+                write!(f, "cannot expand response file `@{}`: {}", path, reason)
+            }
+            ParseError::ConflictingValues {
+                flag,
+                first,
+                second,
+            } => write!(
+                f,
+                "option `{}` was given conflicting values `{}` and `{}`",
+                flag, first, second
+            ),
+            ParseError::OptionAfterPositional(arg) => {
+                write!(f, "option `{}` must come before positional arguments", arg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Decides which value wins when a single-valued flag is given more than once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResolvePolicy {
+    /// The first value given wins.
+    First,
+    /// The last value given wins, as in most shells' override conventions.
+    Last,
+    /// Repeating the same value is allowed, but differing values are a conflict.
+    ErrorOnConflict,
+}
+
+/// Declares one flag accepted by a [`Schema`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlagSpec {
+    /// The canonical flag name, including hyphens (e.g., `"--output"`).
+    pub name: String,
+
+    /// Alternative spellings resolved to `name` (e.g., `["-o", "--out"]`).
+    pub aliases: Vec<String>,
+
+    /// Whether the flag binds a value.
+    pub takes_value: bool,
+}
+
+/// Describes the flags a program accepts, for use with [`parse_with_schema`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+    /// The declared flags.
+    pub flags: Vec<FlagSpec>,
+}
+
+impl Schema {
+    /// Creates an empty `Schema`.
+    pub fn new() -> Self {
+        Schema::default()
+    }
+
+    /// Adds a flag declaration and returns the schema, for chaining.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The canonical flag name, including hyphens (e.g., `"--output"`).
+    /// * `aliases` - Alternative spellings (e.g., `&["-o"]`).
+    /// * `takes_value` - Whether the flag binds a value.
+    pub fn flag(mut self, name: &str, aliases: &[&str], takes_value: bool) -> Self {
+        self.flags.push(FlagSpec {
+            name: name.to_string(),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            takes_value,
+        });
+        self
+    }
+
+    /// Parses arguments against this schema, rejecting undeclared flags.
+    ///
+    /// This is the method form of [`parse_with_schema`]; see there for how values are bound
+    /// and aliases renamed. Use [`get`] or [`Command::parse_from`] for permissive parsing.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Command`, or `ParseError::UnknownOption` for the first undeclared flag.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::cmd_arg::{ParseError, Schema};
+    ///
+    /// let schema = Schema::new().flag("--verbose", &["-v"], false);
+    /// assert!(schema.parse_from(["prog", "-v"]).is_ok());
+    /// assert_eq!(
+    ///     schema.parse_from(["prog", "-x"]).unwrap_err(),
+    ///     ParseError::UnknownOption("-x".to_string())
+    /// );
+    /// ```
+    pub fn parse_from<I, S>(&self, args: I) -> Result<Command, ParseError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        parse_with_schema(args, self)
+    }
+
+    /// Finds the declaration whose name or aliases match the parsed option.
+    fn lookup(&self, opt: &Arg) -> std::option::Option<&FlagSpec> {
+        self.flags.iter().find(|spec| {
+            opt.matches(&spec.name) || spec.aliases.iter().any(|alias| opt.matches(alias))
+        })
+    }
+}
+
+/// Parses arguments according to a [`Schema`] in one call.
+///
+/// On top of the normal classification, this:
+/// - binds values to value-taking flags given as `--output=x`, `--output x`, `-o x`,
+///   `-ox`, or `-o=x`. A separate value must not itself look like an option, and is
+///   split on commas just like an attached one;
+/// - renames every alias to its canonical name (e.g., `-o` becomes `--output`);
+/// - rejects any flag the schema does not declare.
+///
+/// # Arguments
+///
+/// * `args` - The arguments to parse, including the command name.
+/// * `schema` - The accepted flags.
+///
+/// # Returns
+///
+/// The parsed `Command`, or `ParseError::UnknownOption` for the first undeclared flag.
+///
+/// # Examples
+///
+/// ```
+/// use cmd_arg::cmd_arg::{parse_with_schema, Schema};
+///
+/// let schema = Schema::new()
+///     .flag("--output", &["-o"], true)
+///     .flag("--verbose", &["-v"], false);
+/// let command = parse_with_schema(["prog", "-v", "-o", "out.txt", "in.txt"], &schema).unwrap();
+/// assert_eq!(command.opts[1].opt_str, "--output");
+/// assert_eq!(command.opts[1].opt_values, ["out.txt"]);
+/// assert_eq!(command.opts[2].opt_str, "in.txt");
+/// ```
+pub fn parse_with_schema<I, S>(args: I, schema: &Schema) -> Result<Command, ParseError>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let value_names: Vec<&String> = schema
+        .flags
+        .iter()
+        .filter(|spec| spec.takes_value)
+        .flat_map(|spec| std::iter::once(&spec.name).chain(&spec.aliases))
+        .collect();
+    let value_shorts = value_names
+        .iter()
+        .filter_map(|name| {
+            let mut chars = name.strip_prefix('-')?.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c != '-' => Some(c),
+                _ => None,
+            }
+        })
+        .collect();
+    let value_longs = value_names
+        .iter()
+        .filter(|name| name.starts_with("--"))
+        .map(|name| name.to_string())
+        .collect();
+    let config = ParseConfig {
+        value_shorts,
+        value_longs,
+        long_values_require_equals: false,
+        ..ParseConfig::default()
+    };
+    let mut command = Command::parse_with_config(args, &config);
+
+    for opt in &mut command.opts {
+        if opt.opt_type != OptionType::Simple {
+            let spec = schema
+                .lookup(opt)
+                .ok_or_else(|| ParseError::UnknownOption(opt.opt_str.clone()))?;
+            opt.opt_str = spec.name.clone();
+        }
+    }
+    Ok(command)
+}
+
+/// A read-only view of a [`Command`] with clap-style query methods, for easier migration.
+///
+/// Created with [`Command::matches`]. Each method maps onto an existing query:
+///
+/// | `Matches` method | Equivalent on `Command` |
+/// |------------------|-------------------------|
+/// | `is_present(name)` | `has_flag(name)` |
+/// | `value_of(name)` | `first_value_of_any(&[name])` |
+/// | `values_of(name)` | the values of every occurrence, in parse order |
+///
+/// Unlike clap, nothing is declared up front: names are accepted with or without leading
+/// hyphens (`"output"` or `"--output"`), a flag given several times contributes all of its
+/// values, and `value_of` never fails on multiple values but returns the first one.
+/// Positional arguments cannot be looked up by name.
+#[derive(Debug, Clone, Copy)]
+pub struct Matches<'a> {
+    command: &'a Command,
+}
+
+impl<'a> Matches<'a> {
+    /// Checks whether the named flag occurs at least once.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to look up, with or without leading hyphens.
+    pub fn is_present(&self, name: &str) -> bool {
+        self.command.has_flag(name)
+    }
+
+    /// Returns the first value given to the named flag.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to look up, with or without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// The first value, or `None` if the flag is absent or carries no values.
+    pub fn value_of(&self, name: &str) -> std::option::Option<&'a str> {
+        self.command.first_value_of_any(&[name])
+    }
+
+    /// Returns every value given to the named flag, across all of its occurrences.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The flag to look up, with or without leading hyphens.
+    ///
+    /// # Returns
+    ///
+    /// The values in parse order, or `None` if the flag is absent. A flag present without
+    /// values yields an empty vector.
+    pub fn values_of(&self, name: &str) -> std::option::Option<Vec<&'a str>> {
+        self.is_present(name).then(|| {
+            self.command
+                .opts
+                .iter()
+                .filter(|o| o.matches(name))
+                .flat_map(|o| o.opt_values.iter().map(String::as_str))
+                .collect()
+        })
+    }
+}
+
+/// Supplies the text that response file arguments refer to.
+///
+/// Used by [`Command::parse_with_response_files`]. [`SystemResponseSource`] reads the file
+/// system and the process's standard input; tests and embedders can provide their own.
+pub trait ResponseSource {
+    /// Reads the whole response file at `path` (the text after `@`).
+    fn read_file(&mut self, path: &str) -> io::Result<String>;
+
+    /// Reads the rest of standard input, for the `@-` argument.
+    fn read_stdin(&mut self) -> io::Result<String>;
+}
+
+/// A [`ResponseSource`] backed by the file system and the process's standard input.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResponseSource;
+
+impl ResponseSource for SystemResponseSource {
+    fn read_file(&mut self, path: &str) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn read_stdin(&mut self) -> io::Result<String> {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        Ok(text)
+    }
+}
+
+/// Encodes `s` as a JSON string literal, including the surrounding quotes.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Encodes a list of strings as a JSON array.
+fn json_array(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|item| json_string(item)).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// Writes one option value so that the default list parsing reads it back unchanged.
+///
+/// Empty values, values containing `,`, and values with surrounding whitespace are wrapped
+/// in double quotes; anything else is returned as is.
+fn list_value(value: &str) -> String {
+    if value.is_empty() || value.contains(',') || value.trim() != value {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Quotes `word` for a POSIX shell, using double quotes unless it is made only of safe
+/// characters. Inside the quotes, `"`, `\`, `$`, and `` ` `` are escaped with a backslash.
+///
+/// A word containing `!` is single-quoted instead, with embedded `'` written as `'\''`,
+/// because interactive bash expands `!` as history even inside double quotes.
+fn shell_quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-+=:,./@%".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        return word.to_string();
+    }
+    if word.contains('!') {
+        return format!("'{}'", word.replace('\'', "'\\''"));
+    }
+    let mut quoted = String::from("\"");
+    for c in word.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Appends `words` to `expanded`, replacing response file tokens with their words.
+///
+/// # Arguments
+///
+/// * `words` - The words to expand.
+/// * `config` - The settings providing the separator that ends expansion.
+/// * `source` - Where response file and standard input contents are read from.
+/// * `open` - The paths of the response files currently being expanded, outermost first.
+/// * `expanded` - Receives the expanded words.
+///
+/// # Returns
+///
+/// `true` if the separator was reached, after which the remaining words were copied
+/// literally.
+fn expand_response_files<I, R>(
+    mut words: I,
+    config: &ParseConfig,
+    source: &mut R,
+    open: &mut Vec<String>,
+    expanded: &mut Vec<String>,
+) -> Result<bool, ParseError>
+where
+    I: Iterator<Item = String>,
+    R: ResponseSource,
+{
+    while let Some(word) = words.next() {
+        if config.is_separator(&word) {
+            expanded.push(word);
+            expanded.extend(words);
+            return Ok(true);
+        }
+        let Some(path) = word.strip_prefix('@') else {
+            expanded.push(word);
+            continue;
+        };
+        let response_error = |reason: String| ParseError::ResponseFile {
+            path: path.to_string(),
+            reason,
+        };
+        if open.iter().any(|p| p == path) {
+            return Err(response_error("response file refers to itself".to_string()));
+        }
+        let text = if path == "-" {
+            source.read_stdin()
+        } else {
+            source.read_file(path)
+        };
+        let text = text.map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => ParseError::ResponseFileNotFound(path.to_string()),
+            _ => response_error(e.to_string()),
+        })?;
+        let inner = shell_split(&text).map_err(|e| response_error(e.to_string()))?;
+        open.push(path.to_string());
+        let separated = expand_response_files(inner.into_iter(), config, source, open, expanded)?;
+        open.pop();
+        if separated {
+            expanded.extend(words);
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    fn parse(args: &[&str]) -> Command {
+        Command::parse_with_config(args.iter().copied(), &ParseConfig::default())
+    }
+
+    fn opt_strs(command: &Command) -> Vec<&str> {
+        command.opts.iter().map(|o| o.opt_str.as_str()).collect()
+    }
+
+    fn value_shorts(chars: &[char]) -> ParseConfig {
+        ParseConfig {
+            value_shorts: chars.to_vec(),
+            ..ParseConfig::default()
+        }
+    }
+
+    #[test]
+    fn short_value_with_equals() {
+        let command = Command::parse_with_config(["prog", "-n=5"], &value_shorts(&['n']));
+        assert_eq!(opt_strs(&command), ["-n"]);
+        assert_eq!(command.opts[0].opt_values, ["5"]);
+    }
+
+    #[test]
+    fn short_value_attached() {
+        let command = Command::parse_with_config(["prog", "-n5"], &value_shorts(&['n']));
+        assert_eq!(opt_strs(&command), ["-n"]);
+        assert_eq!(command.opts[0].opt_values, ["5"]);
+    }
+
+    #[test]
+    fn short_bundle_then_value() {
+        let command = Command::parse_with_config(["prog", "-xn=5"], &value_shorts(&['n']));
+        assert_eq!(opt_strs(&command), ["-x", "-n"]);
+        assert!(command.opts[0].opt_values.is_empty());
+        assert_eq!(command.opts[1].opt_values, ["5"]);
+    }
+
+    #[test]
+    fn short_value_flag_without_value() {
+        let command = Command::parse_with_config(["prog", "-xn"], &value_shorts(&['n']));
+        assert_eq!(opt_strs(&command), ["-x", "-n"]);
+        assert!(command.opts[1].opt_values.is_empty());
+    }
+
+    #[test]
+    fn default_config_attaches_equals_value() {
+        let command = Command::parse_with_config(["prog", "-n=5"], &ParseConfig::default());
+        assert_eq!(opt_strs(&command), ["-n"]);
+        assert_eq!(command.opts[0].opt_values, ["5"]);
+    }
+
+    #[test]
+    fn first_value_of_any_uses_parse_order() {
+        let command = parse(&["prog", "--out=a.txt", "-o=b.txt", "--output=c.txt"]);
+        assert_eq!(
+            command.first_value_of_any(&["--output", "--out", "-o"]),
+            Some("a.txt")
+        );
+        assert_eq!(
+            command.first_value_of_any(&["-o", "--output"]),
+            Some("b.txt")
+        );
+        assert_eq!(command.first_value_of_any(&["--output"]), Some("c.txt"));
+    }
+
+    #[test]
+    fn first_value_of_any_skips_valueless_and_missing() {
+        let command = parse(&["prog", "--output", "file.txt", "--out=x"]);
+        assert_eq!(
+            command.first_value_of_any(&["--out", "--output"]),
+            Some("x")
+        );
+        assert_eq!(command.first_value_of_any(&["--missing", "file.txt"]), None);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn to_toml_round_trips_through_toml_parser() {
+        let command = parse(&[
+            "prog",
+            "--out=a.txt",
+            "--tag=x,y",
+            "--force",
+            "--inc=a",
+            "--inc=b",
+            "input.txt",
+            "-v",
+            "--",
+            "rest",
+        ]);
+        let table: toml::Table = command.to_toml().parse().unwrap();
+
+        let options = table["options"].as_table().unwrap();
+        assert_eq!(options["out"].as_str(), Some("a.txt"));
+        assert_eq!(options["tag"], toml::Value::from(vec!["x", "y"]));
+        assert_eq!(options["inc"], toml::Value::from(vec!["a", "b"]));
+        assert_eq!(options["force"].as_bool(), Some(true));
+        assert!(!options.contains_key("v"));
+        assert_eq!(table["positionals"], toml::Value::from(vec!["input.txt"]));
+        assert_eq!(table["args"], toml::Value::from(vec!["rest"]));
+    }
+
+    #[test]
+    fn flag_names_are_trimmed_by_default() {
+        let command = parse(&["prog", "--data =x", "--name\t=y"]);
+        assert_eq!(opt_strs(&command), ["--data", "--name"]);
+        assert_eq!(command.opts[0].opt_values, ["x"]);
+    }
+
+    #[test]
+    fn flag_name_trimming_can_be_disabled() {
+        let config = ParseConfig {
+            trim_flag_names: false,
+            ..ParseConfig::default()
+        };
+        let command = Command::parse_with_config(["prog", "--data =x"], &config);
+        assert_eq!(opt_strs(&command), ["--data "]);
+    }
+
+    #[test]
+    fn set_subcommand_replaces_leading_simple() {
+        let mut command = parse(&["prog", "ci", "--fast", "src"]);
+        command.set_subcommand("check");
+        assert_eq!(opt_strs(&command), ["check", "--fast", "src"]);
+    }
+
+    #[test]
+    fn set_subcommand_inserts_when_missing() {
+        let mut command = parse(&["prog", "--fast", "src"]);
+        command.set_subcommand("check");
+        assert_eq!(opt_strs(&command), ["check", "--fast", "src"]);
+        assert_eq!(command.opts[0].opt_type, OptionType::Simple);
+
+        let mut empty = parse(&["prog"]);
+        empty.set_subcommand("check");
+        assert_eq!(opt_strs(&empty), ["check"]);
+    }
+
+    #[test]
+    fn append_syntax_is_marked() {
+        let command = parse(&["prog", "--def+=x", "--def=y", "--def+=a,b"]);
+        assert_eq!(opt_strs(&command), ["--def", "--def", "--def"]);
+        assert!(command.opts[0].append);
+        assert_eq!(command.opts[0].opt_values, ["x"]);
+        assert!(!command.opts[1].append);
+        assert_eq!(command.opts[1].opt_values, ["y"]);
+        assert!(command.opts[2].append);
+        assert_eq!(command.opts[2].opt_values, ["a", "b"]);
+    }
+
+    #[test]
+    fn shell_split_handles_mixed_quoting() {
+        let words = shell_split(r#"  a  "b 'c' d"  'e "f" g'  h"i"'j'  "#).unwrap();
+        assert_eq!(words, ["a", "b 'c' d", "e \"f\" g", "hij"]);
+    }
+
+    #[test]
+    fn shell_split_handles_escapes_and_empty_words() {
+        let words = shell_split(r#"a\ b "q\"uote" "back\\slash" "keep\n" '' "" end\"#).unwrap();
+        assert_eq!(
+            words,
+            ["a b", "q\"uote", "back\\slash", "keep\\n", "", "", "end\\"]
+        );
+        assert_eq!(shell_split("   ").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn shell_split_rejects_unterminated_quotes() {
+        assert_eq!(
+            shell_split("a 'b c"),
+            Err(TokenizeError::UnterminatedQuote('\''))
+        );
+        assert_eq!(
+            shell_split(r#"a "b\"#),
+            Err(TokenizeError::UnterminatedQuote('"'))
+        );
+    }
+
+    #[test]
+    fn strip_flag_prefix_renames_matching_long_options() {
+        let mut command = parse(&[
+            "prog",
+            "--myapp-verbose",
+            "--myapp-out=x",
+            "--other",
+            "--myapp-",
+            "-m",
+            "myapp-file",
+        ]);
+        command.strip_flag_prefix("myapp-");
+        assert_eq!(
+            opt_strs(&command),
+            [
+                "--verbose",
+                "--out",
+                "--other",
+                "--myapp-",
+                "-m",
+                "myapp-file"
+            ]
+        );
+        assert_eq!(command.opts[1].opt_values, ["x"]);
+    }
+
+    #[test]
+    fn validate_known_lists_unknown_flags() {
+        let command = parse(&["prog", "--verbose", "--xyz", "-q", "file.txt"]);
+        assert_eq!(
+            command.validate_known(&["verbose", "-q"]),
+            Err(vec!["--xyz".to_string()])
+        );
+        assert_eq!(command.validate_known(&["--verbose", "--xyz", "q"]), Ok(()));
+    }
+
+    #[test]
+    fn suggest_picks_closest_candidate() {
+        assert_eq!(
+            suggest("--verbsoe", &["--version", "--verbose"]),
+            Some("--verbose")
+        );
+        assert_eq!(suggest("--colour", &["color", "count"]), Some("color"));
+        assert_eq!(suggest("-x", &["-v"]), Some("-v"));
+        assert_eq!(suggest("--output", &["--input"]), None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "unknown option(s): `--verbsoe` (did you mean `--verbose`?), `--xyz`"
+    )]
+    fn expect_known_panics_with_hints() {
+        let command = parse(&["prog", "--verbsoe", "--xyz", "--quiet"]);
+        command.expect_known(&["--verbose", "--quiet"]);
+    }
+
+    #[test]
+    fn expect_known_accepts_known_flags() {
+        let command = parse(&["prog", "--verbose", "file.txt"]);
+        command.expect_known(&["verbose"]);
+    }
+
+    #[test]
+    fn multi_group_splits_on_every_separator() {
+        let config = ParseConfig {
+            multi_group: true,
+            ..ParseConfig::default()
+        };
+        let command = Command::parse_with_config(
+            ["prog", "-v", "a", "--", "b", "-c", "--", "--", "d"],
+            &config,
+        );
+        assert_eq!(opt_strs(&command), ["-v", "a"]);
+        assert!(command.args.is_empty());
+        assert_eq!(
+            command.arg_groups,
+            [vec!["a"], vec!["b", "-c"], vec![], vec!["d"]]
+        );
+    }
+
+    #[test]
+    fn multi_group_without_separator_has_one_group() {
+        let config = ParseConfig {
+            multi_group: true,
+            ..ParseConfig::default()
+        };
+        let command = Command::parse_with_config(["prog", "x", "--y"], &config);
+        assert_eq!(command.arg_groups, [vec!["x"]]);
+
+        let single = parse(&["prog", "a", "--", "b", "--", "c"]);
+        assert!(single.arg_groups.is_empty());
+        assert_eq!(single.args, ["b", "--", "c"]);
+    }
+
+    #[test]
+    fn into_value_map_merges_repeated_flags() {
+        let command = parse(&["prog", "--inc=a", "-v", "--inc=b,c", "file", "--", "x"]);
+        let map = command.into_value_map();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["--inc"], ["a", "b", "c"]);
+        assert!(map["-v"].is_empty());
+    }
+
+    #[test]
+    fn leading_separator_makes_everything_trailing() {
+        let command = parse(&["prog", "--", "-v", "--data=x", "file"]);
+        assert!(command.opts.is_empty());
+        assert_eq!(command.args, ["-v", "--data=x", "file"]);
+        assert!(command.all_trailing());
+
+        assert!(!parse(&["prog", "-v", "--", "file"]).all_trailing());
+        assert!(!parse(&["prog", "--"]).all_trailing());
+        assert!(!parse(&["prog"]).all_trailing());
+    }
+
+    #[test]
+    fn merge_defaults_from_str_keeps_existing_flags() {
+        let mut command = parse(&["prog", "--jobs=8", "file.txt"]);
+        command
+            .merge_defaults_from_str("--jobs=4 --color='always on' -v --inc=a --inc=b extra")
+            .unwrap();
+        assert_eq!(
+            opt_strs(&command),
+            ["--jobs", "file.txt", "--color", "-v", "--inc", "--inc"]
+        );
+        assert_eq!(command.opts[0].opt_values, ["8"]);
+        assert_eq!(command.opts[2].opt_values, ["always on"]);
+    }
+
+    #[test]
+    fn merge_defaults_from_str_reports_tokenize_errors() {
+        let mut command = parse(&["prog"]);
+        assert_eq!(
+            command.merge_defaults_from_str("--x='open"),
+            Err(TokenizeError::UnterminatedQuote('\''))
+        );
+        assert!(command.opts.is_empty());
+    }
+
+    #[test]
+    fn get_parsed_vec_parses_all_values() {
+        let command = parse(&["prog", "--ports=80,443,8080", "--ids=1,x,3", "--empty"]);
+        assert_eq!(
+            command.get_parsed_vec::<u16>("--ports"),
+            Some(Ok(vec![80, 443, 8080]))
+        );
+        assert_eq!(
+            command
+                .get_parsed_vec::<u16>("ports")
+                .unwrap()
+                .unwrap()
+                .len(),
+            3
+        );
+        assert!(matches!(
+            command.get_parsed_vec::<u32>("--ids"),
+            Some(Err(_))
+        ));
+        assert_eq!(command.get_parsed_vec::<u32>("--empty"), Some(Ok(vec![])));
+        assert_eq!(command.get_parsed_vec::<u32>("--missing"), None);
+    }
+
+    #[test]
+    fn get_parsed_vec_ignores_short_options() {
+        let command = Command::parse_with_config(["prog", "-p80"], &value_shorts(&['p']));
+        assert_eq!(command.get_parsed_vec::<u16>("p"), None);
+        let command =
+            Command::parse_with_config(["prog", "-p80", "--p=443"], &value_shorts(&['p']));
+        assert_eq!(command.get_parsed_vec::<u16>("p"), Some(Ok(vec![443])));
+    }
+
+    #[test]
+    fn retain_values_filters_across_occurrences() {
+        let mut command = parse(&["prog", "--inc=a,b,a", "--other=a", "--inc=b,c"]);
+        let mut seen = std::collections::HashSet::new();
+        command.retain_values("inc", |v| seen.insert(v.to_string()));
+        assert_eq!(command.opts[0].opt_values, ["a", "b"]);
+        assert_eq!(command.opts[1].opt_values, ["a"]);
+        assert_eq!(command.opts[2].opt_values, ["c"]);
+    }
+
+    #[test]
+    fn retain_values_ignores_short_options() {
+        let mut command =
+            Command::parse_with_config(["prog", "-i5", "--i=5,6"], &value_shorts(&['i']));
+        command.retain_values("i", |v| v != "5");
+        assert_eq!(command.opts[0].opt_values, ["5"]);
+        assert_eq!(command.opts[1].opt_values, ["6"]);
+    }
+
+    #[test]
+    fn retain_values_leaves_valueless_flag() {
+        let mut command = parse(&["prog", "--inc=x"]);
+        command.retain_values("--inc", |v| v != "x");
+        assert_eq!(opt_strs(&command), ["--inc"]);
+        assert!(command.opts[0].opt_values.is_empty());
+    }
+
+    #[test]
+    fn lint_warns_about_hyphen_in_short_bundle() {
+        let command = parse(&["prog", "-x-y"]);
+        assert_eq!(opt_strs(&command), ["-x", "--", "-y"]);
+        let warnings = command.lint();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("option 2: short option bundle contains `-`"));
+
+        assert!(
+            parse(&["prog", "-xy", "--long", "--", "-x-y"])
+                .lint()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn has_flag_with_value_ignores_short_options() {
+        let command = Command::parse_with_config(["prog", "-capp.toml"], &value_shorts(&['c']));
+        assert!(!command.has_flag_with_value("c"));
+    }
+
+    #[test]
+    fn has_flag_with_value_distinguishes_states() {
+        let absent = parse(&["prog", "config"]);
+        assert!(!absent.has_flag_with_value("--config"));
+
+        let valueless = parse(&["prog", "--config", "--config="]);
+        assert!(!valueless.has_flag_with_value("--config"));
+
+        let with_value = parse(&["prog", "--config", "--config=app.toml"]);
+        assert!(with_value.has_flag_with_value("--config"));
+        assert!(with_value.has_flag_with_value("config"));
+    }
+
+    #[test]
+    fn annotations_are_collected_separately() {
+        let config = ParseConfig {
+            annotation_prefix: Some('@'),
+            ..ParseConfig::default()
+        };
+        let command = Command::parse_with_config(
+            ["prog", "@note", "-v", "@build=42", "file", "--", "@kept"],
+            &config,
+        );
+        assert_eq!(command.annotations, ["note", "build=42"]);
+        assert_eq!(opt_strs(&command), ["-v", "file"]);
+        assert_eq!(command.args, ["@kept"]);
+
+        let plain = parse(&["prog", "@note"]);
+        assert!(plain.annotations.is_empty());
+        assert_eq!(opt_strs(&plain), ["@note"]);
+    }
+
+    fn schema() -> Schema {
+        Schema::new()
+            .flag("--output", &["-o", "--out"], true)
+            .flag("--verbose", &["-v"], false)
+    }
+
+    #[test]
+    fn parse_with_schema_binds_values_in_every_form() {
+        for args in [
+            &["prog", "--output=a.txt", "in"][..],
+            &["prog", "--output", "a.txt", "in"],
+            &["prog", "--out", "a.txt", "in"],
+            &["prog", "-o", "a.txt", "in"],
+        ] {
+            let command = parse_with_schema(args.iter().copied(), &schema()).unwrap();
+            assert_eq!(opt_strs(&command), ["--output", "in"]);
+            assert_eq!(command.opts[0].opt_values, ["a.txt"]);
+        }
+        let attached = parse_with_schema(["prog", "-voa.txt"], &schema()).unwrap();
+        assert_eq!(opt_strs(&attached), ["--verbose", "--output"]);
+        assert_eq!(attached.opts[1].opt_values, ["a.txt"]);
+    }
+
+    #[test]
+    fn parse_with_schema_does_not_bind_boolean_flags() {
+        let command = parse_with_schema(["prog", "-v", "in", "--", "-x"], &schema()).unwrap();
+        assert_eq!(opt_strs(&command), ["--verbose", "in"]);
+        assert!(command.opts[0].opt_values.is_empty());
+        assert_eq!(command.args, ["-x"]);
+
+        let trailing = parse_with_schema(["prog", "--output"], &schema()).unwrap();
+        assert!(trailing.opts[0].opt_values.is_empty());
+    }
+
+    #[test]
+    fn parse_with_schema_rejects_unknown_flags() {
+        assert_eq!(
+            parse_with_schema(["prog", "-v", "--verbsoe"], &schema()).unwrap_err(),
+            ParseError::UnknownOption("--verbsoe".to_string())
+        );
+        assert_eq!(
+            schema().parse_from(["prog", "-o", "x", "-q"]).unwrap_err(),
+            ParseError::UnknownOption("-q".to_string())
+        );
+        let command = schema()
+            .parse_from(["prog", "--out=x", "--", "-q"])
+            .unwrap();
+        assert_eq!(opt_strs(&command), ["--output"]);
+        assert_eq!(command.args, ["-q"]);
+    }
+
+    #[test]
+    fn value_diff_ignores_short_options() {
+        let config = value_shorts(&['i']);
+        let before = Command::parse_with_config(["prog", "-ix", "--i=a"], &config);
+        let after = Command::parse_with_config(["prog", "--i=a,b"], &config);
+        assert_eq!(before.value_diff(&after, "i"), (vec![], vec!["b"]));
+        assert_eq!(after.value_diff(&before, "i"), (vec!["b"], vec![]));
+    }
+
+    #[test]
+    fn value_diff_reports_removed_and_added_values() {
+        let before = parse(&["prog", "--inc=a,b", "--inc=c"]);
+        let after = parse(&["prog", "--inc=b", "--inc=c,d,e"]);
+        assert_eq!(
+            before.value_diff(&after, "inc"),
+            (vec!["a"], vec!["d", "e"])
+        );
+        assert_eq!(
+            after.value_diff(&before, "--inc"),
+            (vec!["d", "e"], vec!["a"])
+        );
+
+        let none = parse(&["prog"]);
+        assert_eq!(
+            none.value_diff(&before, "inc"),
+            (vec![], vec!["a", "b", "c"])
+        );
+    }
+
+    #[test]
+    fn parser_matches_stateless_parsing() {
+        let lines: [&[&str]; 4] = [
+            &["repl", "--verbose", "-abc", "--data=a,b", "input.txt"],
+            &["repl", "-xn5", "@note", "--data=a,b", "--", "-v", "--", "x"],
+            &["repl", "-", "--data =x", "--=x", "--", "--verbose"],
+            &["repl"],
+        ];
+        let configs = [
+            ParseConfig::default(),
+            ParseConfig {
+                value_shorts: vec!['n'],
+                multi_group: true,
+                annotation_prefix: Some('@'),
+                ..ParseConfig::default()
+            },
+        ];
+        for config in configs {
+            let mut parser = Parser::new(config.clone());
+            // The second round runs entirely from the cache.
+            for _ in 0..2 {
+                for line in lines {
+                    assert_eq!(
+                        format!("{:?}", parser.parse(line.iter().copied())),
+                        format!(
+                            "{:?}",
+                            Command::parse_with_config(line.iter().copied(), &config)
+                        ),
+                        "{:?}",
+                        line
+                    );
+                }
+            }
+            assert!(parser.cache.contains_key("--verbose"));
+        }
+    }
+
+    #[test]
+    fn parser_cache_is_bounded() {
+        let mut parser = Parser::default();
+        for i in 0..PARSER_CACHE_CAPACITY + 10 {
+            let command = parser.parse(["prog".to_string(), format!("--opt{}", i)]);
+            assert_eq!(command.opts[0].opt_type, OptionType::LongOpt);
+        }
+        assert!(parser.cache.len() <= PARSER_CACHE_CAPACITY);
+    }
+
+    #[test]
+    fn min_values_is_enforced_by_fallible_parser() {
+        let config = ParseConfig {
+            min_values: vec![("--range".to_string(), 2)],
+            ..ParseConfig::default()
+        };
+        let err = Command::try_parse_with_config(["prog", "--range=1,5", "--range=3"], &config)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::TooFewValues {
+                flag: "--range".to_string(),
+                got: 1,
+                need: 2
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "option `--range` needs at least 2 value(s) but got 1"
+        );
+
+        let ok = Command::try_parse_with_config(["prog", "--range=1,5", "other"], &config).unwrap();
+        assert_eq!(ok.opts[0].opt_values, ["1", "5"]);
+        assert!(Command::try_parse_with_config(["prog", "--range"], &config).is_err());
+        assert!(Command::try_parse_with_config(["prog", "-v"], &config).is_ok());
+    }
+
+    #[test]
+    fn structured_options_split_on_colon() {
+        let config = ParseConfig {
+            structured_options: true,
+            ..ParseConfig::default()
+        };
+        let command = Command::parse_with_config(
+            [
+                "prog",
+                "--set:database.host=localhost",
+                "--set:ports=80,443",
+                "--url=http://x",
+                "--set:",
+            ],
+            &config,
+        );
+        assert_eq!(opt_strs(&command), ["--set", "--set", "--url", "--set"]);
+        assert_eq!(command.opts[0].opt_values, ["database.host=localhost"]);
+        assert_eq!(command.opts[1].opt_values, ["ports=80,443"]);
+        assert_eq!(command.opts[2].opt_values, ["http://x"]);
+        assert!(command.opts[3].opt_values.is_empty());
+        assert_eq!(
+            command.structured_sets(),
+            [
+                ("--set", "database.host", "localhost"),
+                ("--set", "ports", "80,443")
+            ]
+        );
+    }
+
+    #[test]
+    fn structured_options_are_off_by_default() {
+        let command = parse(&["prog", "--set:a=b"]);
+        assert_eq!(opt_strs(&command), ["--set:a"]);
+        assert_eq!(command.opts[0].opt_values, ["b"]);
+    }
+
+    #[test]
+    fn rename_value_ignores_short_options() {
+        let mut command =
+            Command::parse_with_config(["prog", "-mlegacy", "--m=legacy"], &value_shorts(&['m']));
+        command.rename_value("m", "legacy", "compat");
+        assert_eq!(command.opts[0].opt_values, ["legacy"]);
+        assert_eq!(command.opts[1].opt_values, ["compat"]);
+    }
+
+    #[test]
+    fn rename_value_rewrites_matching_values_only() {
+        let mut command = parse(&[
+            "prog",
+            "--mode=legacy",
+            "--other=legacy",
+            "--mode=fast,legacy",
+        ]);
+        command.rename_value("mode", "legacy", "compat");
+        assert_eq!(command.opts[0].opt_values, ["compat"]);
+        assert_eq!(command.opts[1].opt_values, ["legacy"]);
+        assert_eq!(command.opts[2].opt_values, ["fast", "compat"]);
+    }
+
+    fn space_values(names: &[&str]) -> ParseConfig {
+        ParseConfig {
+            value_longs: names.iter().map(|name| name.to_string()).collect(),
+            long_values_require_equals: false,
+            ..ParseConfig::default()
+        }
+    }
+
+    #[test]
+    fn long_values_require_equals_by_default() {
+        let config = ParseConfig {
+            value_longs: vec!["--output".to_string()],
+            ..ParseConfig::default()
+        };
+        let cmd = Command::try_parse_with_config(
+            ["prog", "--output=a.txt", "--output", "b.txt"],
+            &config,
+        )
+        .unwrap();
+        assert_eq!(opt_strs(&cmd), vec!["--output", "--output", "b.txt"]);
+        assert_eq!(cmd.opts[0].opt_values, vec!["a.txt"]);
+        assert!(cmd.opts[1].opt_values.is_empty());
+    }
+
+    #[test]
+    fn space_separated_long_values() {
+        let config = space_values(&["output"]);
+        let cmd = Command::try_parse_with_config(
+            [
+                "prog",
+                "--output",
+                "b.txt",
+                "--verbose",
+                "file",
+                "--output",
+                "-v",
+            ],
+            &config,
+        )
+        .unwrap();
+        assert_eq!(
+            opt_strs(&cmd),
+            vec!["--output", "--verbose", "file", "--output", "-v"]
+        );
+        assert_eq!(cmd.opts[0].opt_values, vec!["b.txt"]);
+        assert!(cmd.opts[1].opt_values.is_empty());
+        assert!(cmd.opts[3].opt_values.is_empty());
+    }
+
+    #[test]
+    fn space_separated_mode_rejects_equals_form() {
+        let config = space_values(&["--output"]);
+        let err = Command::try_parse_with_config(["prog", "--output=b.txt"], &config).unwrap_err();
+        assert_eq!(err, ParseError::UnexpectedValue("--output".to_string()));
+        assert!(Command::try_parse_with_config(["prog", "--", "--output=b.txt"], &config).is_ok());
+        let lenient = Command::parse_with_config(["prog", "--output=b.txt"], &config);
+        assert_eq!(lenient.opts[0].opt_values, vec!["b.txt"]);
+    }
+
+    #[test]
+    fn clear_values_keeps_presence() {
+        let mut cmd = parse(&["prog", "--mode=legacy,fast", "-v", "--mode+=x", "--other=1"]);
+        cmd.clear_values("mode");
+        assert_eq!(opt_strs(&cmd), vec!["--mode", "-v", "--mode", "--other"]);
+        assert!(cmd.opts[0].opt_values.is_empty());
+        assert!(cmd.opts[2].opt_values.is_empty());
+        assert!(!cmd.opts[2].append);
+        assert_eq!(cmd.opts[3].opt_values, vec!["1"]);
+    }
+
+    #[test]
+    fn get_wide_matches_get_for_unicode_args() {
+        let wide = get_wide();
+        let narrow = get();
+        assert_eq!(wide.cmd_name, narrow.cmd_name);
+        assert_eq!(opt_strs(&wide), opt_strs(&narrow));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn native_to_string_replaces_unpaired_surrogates() {
+        use std::ffi::OsString;
+        use std::os::windows::ffi::OsStringExt;
+
+        let arg = OsString::from_wide(&[0x66, 0xD800, 0x6F]);
+        assert_eq!(native_to_string(&arg), "f\u{FFFD}o");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn native_to_string_replaces_invalid_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let arg = OsStr::from_bytes(b"f\xFFo");
+        assert_eq!(native_to_string(arg), "f\u{FFFD}o");
+    }
+
+    #[test]
+    fn check_deprecated_reports_present_flags_once() {
+        let command = parse(&["prog", "--old", "-q", "--old=1", "old"]);
+        let table = [("--old", "--new"), ("-q", "--quiet"), ("--gone", "--here")];
+        assert_eq!(
+            command.check_deprecated(&table),
+            [
+                "`--old` is deprecated; use `--new`",
+                "`-q` is deprecated; use `--quiet`",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_deprecated_empty_when_absent() {
+        let command = parse(&["prog", "--new", "--", "--old"]);
+        assert!(command.check_deprecated(&[("--old", "--new")]).is_empty());
+    }
+
+    #[test]
+    fn value_delimiters_accept_any_listed_char() {
+        let config = ParseConfig {
+            value_delimiters: vec!['=', ':'],
+            ..ParseConfig::default()
+        };
+        let command = Command::parse_with_config(
+            ["prog", "--mode:fast", "--level=3", "--map:a=b", "--def=x:y"],
+            &config,
+        );
+        assert_eq!(opt_strs(&command), ["--mode", "--level", "--map", "--def"]);
+        assert_eq!(command.opts[0].opt_values, ["fast"]);
+        assert_eq!(command.opts[1].opt_values, ["3"]);
+        assert_eq!(command.opts[2].opt_values, ["a=b"]);
+        assert_eq!(command.opts[3].opt_values, ["x:y"]);
+    }
+
+    #[test]
+    fn value_delimiters_empty_disables_split() {
+        let config = ParseConfig {
+            value_delimiters: Vec::new(),
+            ..ParseConfig::default()
+        };
+        let command = Command::parse_with_config(["prog", "--mode=fast"], &config);
+        assert_eq!(opt_strs(&command), ["--mode=fast"]);
+        assert!(command.opts[0].opt_values.is_empty());
+    }
+
+    #[test]
+    fn truncate_opts_keeps_leading_options_and_args() {
+        let mut command = parse(&["prog", "-v", "--mode=fast", "file.txt", "--", "rest"]);
+        command.truncate_opts(2);
+        assert_eq!(opt_strs(&command), ["-v", "--mode"]);
+        assert_eq!(command.args, ["rest"]);
+        command.truncate_opts(5);
+        assert_eq!(command.opts.len(), 2);
+    }
+
+    #[test]
+    fn short_toggle_reads_either_case() {
+        assert_eq!(parse(&["prog", "-e"]).short_toggle('e'), Some(true));
+        assert_eq!(parse(&["prog", "-xE"]).short_toggle('e'), Some(false));
+        assert_eq!(parse(&["prog", "-x", "e"]).short_toggle('e'), None);
+    }
+
+    #[test]
+    fn short_toggle_rejects_characters_without_a_case_pair() {
+        assert_eq!(parse(&["prog", "-E"]).short_toggle('E'), None);
+        assert_eq!(parse(&["prog", "-1"]).short_toggle('1'), None);
+    }
+
+    #[test]
+    fn short_toggle_last_occurrence_wins() {
+        assert_eq!(parse(&["prog", "-e", "-E"]).short_toggle('e'), Some(false));
+        assert_eq!(parse(&["prog", "-E", "-ve"]).short_toggle('e'), Some(true));
+    }
+
+    #[test]
+    fn to_make_assignments_transforms_long_options() {
+        let command = parse(&[
+            "prog",
+            "--out-dir=build",
+            "-v",
+            "--cflags=-O2,-g",
+            "target",
+            "--verbose",
+        ]);
+        assert_eq!(
+            command.to_make_assignments(),
+            ["OUT_DIR=build", "CFLAGS=-O2 -g", "VERBOSE=1"]
+        );
+    }
+
+    #[test]
+    fn to_make_assignments_empty_without_long_options() {
+        assert!(
+            parse(&["prog", "-v", "file"])
+                .to_make_assignments()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn is_help_only_with_help_alone() {
+        assert!(parse(&["prog", "--help"]).is_help_only());
+        assert!(parse(&["prog", "-h", "--version"]).is_help_only());
+        assert!(!parse(&["prog"]).is_help_only());
+    }
+
+    #[test]
+    fn is_help_only_false_with_other_flags() {
+        assert!(!parse(&["prog", "--help", "--verbose"]).is_help_only());
+        assert!(!parse(&["prog", "-hv"]).is_help_only());
+    }
+
+    #[test]
+    fn is_help_only_false_with_positionals() {
+        assert!(!parse(&["prog", "--help", "file.txt"]).is_help_only());
+        assert!(!parse(&["prog", "--help", "--", "file.txt"]).is_help_only());
+    }
+
+    #[test]
+    fn resolve_single_first_and_last() {
+        let command = parse(&["prog", "--mode=a", "-v", "--mode=b,c"]);
+        assert_eq!(
+            command.resolve_single("mode", ResolvePolicy::First),
+            Some("a")
+        );
+        assert_eq!(
+            command.resolve_single("mode", ResolvePolicy::Last),
+            Some("c")
+        );
+        assert_eq!(command.resolve_single("missing", ResolvePolicy::Last), None);
+    }
+
+    #[test]
+    fn resolve_single_error_on_conflict() {
+        let same = parse(&["prog", "--mode=a", "--mode=a"]);
+        assert_eq!(
+            same.try_resolve_single("--mode", ResolvePolicy::ErrorOnConflict),
+            Ok(Some("a"))
+        );
+
+        let differing = parse(&["prog", "--mode=a", "--mode=a", "--mode=b"]);
+        assert_eq!(
+            differing.try_resolve_single("--mode", ResolvePolicy::ErrorOnConflict),
+            Err(ParseError::ConflictingValues {
+                flag: "--mode".to_string(),
+                first: "a".to_string(),
+                second: "b".to_string(),
+            })
+        );
+        assert_eq!(
+            differing.resolve_single("--mode", ResolvePolicy::ErrorOnConflict),
+            None
+        );
+    }
+
+    #[test]
+    fn matches_is_present() {
+        let command = parse(&["prog", "-v", "--output=a.txt", "verbose"]);
+        let m = command.matches();
+        assert!(m.is_present("v"));
+        assert!(m.is_present("--output"));
+        assert!(!m.is_present("verbose"));
+    }
+
+    #[test]
+    fn matches_value_of_and_values_of() {
+        let command = parse(&["prog", "--tag=a,b", "--flag", "--tag=c"]);
+        let m = command.matches();
+        assert_eq!(m.value_of("tag"), Some("a"));
+        assert_eq!(m.value_of("flag"), None);
+        assert_eq!(m.values_of("--tag"), Some(vec!["a", "b", "c"]));
+        assert_eq!(m.values_of("flag"), Some(Vec::new()));
+        let short = Command::parse_with_config(["prog", "-n5"], &value_shorts(&['n']));
+        assert_eq!(short.matches().values_of("n"), Some(vec!["5"]));
+        assert_eq!(m.values_of("missing"), None);
+    }
+
+    #[test]
+    fn parse_raw_keeps_everything_simple() {
+        let command = Command::parse_raw(["prog", "-v", "--x=1", "--", "file"]);
+        assert_eq!(command.cmd_name, "prog");
+        assert_eq!(opt_strs(&command), ["-v", "--x=1", "--", "file"]);
+        assert!(
+            command
+                .opts
+                .iter()
+                .all(|o| o.opt_type == OptionType::Simple && o.opt_values.is_empty())
+        );
+        assert!(command.args.is_empty());
+    }
+
+    /// A `ResponseSource` serving canned files and a standard input that can be read once.
+    struct FakeSource {
+        files: HashMap<String, String>,
+        stdin: std::option::Option<String>,
+    }
+
+    impl FakeSource {
+        fn new(stdin: &str) -> Self {
+            FakeSource {
+                files: HashMap::new(),
+                stdin: Some(stdin.to_string()),
+            }
+        }
+    }
+
+    impl ResponseSource for FakeSource {
+        fn read_file(&mut self, path: &str) -> io::Result<String> {
+            self.files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+
+        fn read_stdin(&mut self) -> io::Result<String> {
+            Ok(self.stdin.take().unwrap_or_default())
+        }
+    }
+
+    #[test]
+    fn response_stdin_tokens_are_spliced_in_place() {
+        let mut source = FakeSource::new("-x --data='a b'\nfile.txt\n");
+        let command = Command::parse_with_response_files(
+            ["prog", "-v", "@-", "--last", "@-", "--", "@-"],
+            &ParseConfig::default(),
+            &mut source,
+        )
+        .unwrap();
+        assert_eq!(
+            opt_strs(&command),
+            ["-v", "-x", "--data", "file.txt", "--last"]
+        );
+        assert_eq!(command.opts[2].opt_values, ["a b"]);
+        assert_eq!(command.args, ["@-"]);
+    }
+
+    #[test]
+    fn response_empty_stdin_expands_to_nothing() {
+        let mut source = FakeSource::new("  \n");
+        let command = Command::parse_with_response_files(
+            ["prog", "@-", "-v"],
+            &ParseConfig::default(),
+            &mut source,
+        )
+        .unwrap();
+        assert_eq!(opt_strs(&command), ["-v"]);
+    }
+
+    #[test]
+    fn response_file_errors_are_reported() {
+        let mut source = FakeSource::new("'open");
+        source
+            .files
+            .insert("args.txt".to_string(), "--mode=fast".to_string());
+        let config = ParseConfig::default();
+
+        let command =
+            Command::parse_with_response_files(["prog", "@args.txt"], &config, &mut source)
+                .unwrap();
+        assert_eq!(command.opts[0].opt_values, ["fast"]);
+
+        let err =
+            Command::parse_with_response_files(["prog", "@-"], &config, &mut source).unwrap_err();
+        assert!(matches!(err, ParseError::ResponseFile { ref path, .. } if path == "-"));
+        let err = Command::parse_with_response_files(["prog", "@nope"], &config, &mut source)
+            .unwrap_err();
+        assert_eq!(err, ParseError::ResponseFileNotFound("nope".to_string()));
+    }
+
+    #[test]
+    fn response_files_expand_recursively_without_cycles() {
+        let mut source = FakeSource::new("");
+        for (path, text) in [
+            ("outer", "-a @inner @inner -- @inner"),
+            ("inner", "--data=x"),
+            ("self", "-v @loop"),
+            ("loop", "@self"),
+        ] {
+            source.files.insert(path.to_string(), text.to_string());
+        }
+        let config = ParseConfig::default();
+
+        let command =
+            Command::parse_with_response_files(["prog", "@outer", "@inner"], &config, &mut source)
+                .unwrap();
+        assert_eq!(opt_strs(&command), ["-a", "--data", "--data"]);
+        assert_eq!(command.args, ["@inner", "@inner"]);
+
+        let err = Command::parse_with_response_files(["prog", "@self"], &config, &mut source)
+            .unwrap_err();
+        assert!(matches!(err, ParseError::ResponseFile { ref path, .. } if path == "self"));
+    }
+
+    #[test]
+    fn parse_from_with_response_files_reads_the_file_system() {
+        let path = env::temp_dir().join(format!("cmd_arg_test_{}.rsp", std::process::id()));
+        fs::write(&path, "-v --data=a,b\n").unwrap();
+        let arg = format!("@{}", path.display());
+        let command = Command::parse_from_with_response_files(["prog", &arg, "file"]);
+        fs::remove_file(&path).unwrap();
+
+        let command = command.unwrap();
+        assert_eq!(opt_strs(&command), ["-v", "--data", "file"]);
+        assert_eq!(command.opts[1].opt_values, ["a", "b"]);
+
+        let err = Command::parse_from_with_response_files(["prog", &arg]).unwrap_err();
+        assert!(matches!(err, ParseError::ResponseFileNotFound(_)));
+    }
+
+    #[test]
+    fn parse_from_matches_default_config() {
+        let args = ["prog", "-ab", "--data=x,y", "file", "--", "rest"];
+        let command = Command::parse_from(args);
+        let expected = parse(&args);
+        assert_eq!(command, expected);
+    }
+
+    #[test]
+    fn parse_from_empty_and_single_element() {
+        let empty = Command::parse_from(Vec::<String>::new());
+        assert_eq!(empty.cmd_name, "");
+        assert!(empty.opts.is_empty() && empty.args.is_empty());
+
+        let single = Command::parse_from(["prog"]);
+        assert_eq!(single.cmd_name, "prog");
+        assert!(single.opts.is_empty() && single.args.is_empty());
+    }
+
+    #[test]
+    fn detect_colors_honors_no_color_and_tty() {
+        assert!(detect_colors(None, true));
+        assert!(detect_colors(Some(OsStr::new("")), true));
+        assert!(!detect_colors(Some(OsStr::new("1")), true));
+        assert!(!detect_colors(None, false));
+    }
+
+    #[test]
+    fn set_colored_switches_escape_codes() {
+        let command = parse(&["prog", "-v", "--data=a", "--", "rest"]);
+
+        set_colored(false);
+        let plain = format!("{}", command);
+        set_colored(true);
+        let colored = format!("{}", command);
+        set_color_mode(ColorMode::Auto);
+
+        assert!(!plain.contains('\x1b'));
+        assert!(plain.contains("--data (Type: Long Option): Values: [a]"));
+        assert!(colored.contains('\x1b'));
+    }
+
+    #[test]
+    fn parse_with_value_opts_takes_next_argument() {
+        let command = Command::parse_with_value_opts(["prog", "-o", "file.txt", "in"], &['o']);
+        assert_eq!(opt_strs(&command), ["-o", "in"]);
+        assert_eq!(command.opts[0].opt_values, ["file.txt"]);
+    }
+
+    #[test]
+    fn parse_with_value_opts_last_argument_has_no_value() {
+        let command = Command::parse_with_value_opts(["prog", "in", "-o"], &['o', 'f']);
+        assert_eq!(opt_strs(&command), ["in", "-o"]);
+        assert!(command.opts[1].opt_values.is_empty());
+
+        let command = Command::parse_with_value_opts(["prog", "-o", "-v", "--", "x"], &['o']);
+        assert_eq!(opt_strs(&command), ["-o", "-v"]);
+        assert!(command.opts[0].opt_values.is_empty());
+        assert_eq!(command.args, ["x"]);
+    }
+
+    #[test]
+    fn parse_with_value_opts_bundle_attaches_to_value_flag_only() {
+        let command = Command::parse_with_value_opts(["prog", "-abo", "value", "rest"], &['o']);
+        assert_eq!(opt_strs(&command), ["-a", "-b", "-o", "rest"]);
+        assert!(command.opts[0].opt_values.is_empty());
+        assert!(command.opts[1].opt_values.is_empty());
+        assert_eq!(command.opts[2].opt_values, ["value"]);
+    }
+
+    #[test]
+    fn attached_short_value_stops_bundling() {
+        let command = Command::parse_with_value_opts(["prog", "-ofile.txt"], &['o']);
+        assert_eq!(opt_strs(&command), ["-o"]);
+        assert_eq!(command.opts[0].opt_values, ["file.txt"]);
+    }
+
+    #[test]
+    fn attached_short_value_after_boolean_flag() {
+        let command = Command::parse_with_value_opts(["prog", "-vofile", "next"], &['o']);
+        assert_eq!(opt_strs(&command), ["-v", "-o", "next"]);
+        assert!(command.opts[0].opt_values.is_empty());
+        assert_eq!(command.opts[1].opt_values, ["file"]);
+    }
+
+    #[test]
+    fn has_flag_accepts_hyphenated_and_bare_names() {
+        let command = parse(&["prog", "-v", "--verbose", "file"]);
+        assert!(command.has_flag("-v"));
+        assert!(command.has_flag("v"));
+        assert!(command.has_flag("--verbose"));
+        assert!(command.has_flag("verbose"));
+        assert!(!command.has_flag("--v"));
+        assert!(!command.has_flag("file"));
+    }
+
+    #[test]
+    fn has_flag_sees_bundled_short_options() {
+        let command = parse(&["prog", "-xvz", "--", "-q"]);
+        assert!(command.has_flag("v"));
+        assert!(command.has_flag("-z"));
+        assert!(!command.has_flag("q"));
+    }
+
+    #[test]
+    fn get_values_returns_first_occurrence() {
+        let command = parse(&["prog", "--data=a,b", "--data=c"]);
+        assert_eq!(
+            command.get_values("--data"),
+            Some(&["a".to_string(), "b".to_string()][..])
+        );
+        assert_eq!(command.get_values("data"), command.get_values("--data"));
+    }
+
+    #[test]
+    fn get_values_present_without_values_and_absent() {
+        let command = parse(&["prog", "--flag", "data"]);
+        assert_eq!(command.get_values("flag"), Some(&[][..]));
+        assert_eq!(command.get_values("data"), None);
+    }
+
+    #[test]
+    fn count_flag_bundled_and_separate() {
+        assert_eq!(parse(&["prog", "-vvv"]).count_flag("v"), 3);
+        assert_eq!(parse(&["prog", "-v", "-v", "-v"]).count_flag("-v"), 3);
+        assert_eq!(
+            parse(&["prog", "-xv", "--verbose", "-v"]).count_flag("v"),
+            2
+        );
+        assert_eq!(
+            parse(&["prog", "--verbose", "--verbose"]).count_flag("verbose"),
+            2
+        );
+        assert_eq!(parse(&["prog", "-x", "v"]).count_flag("v"), 0);
+    }
+
+    #[test]
+    fn get_parsed_first_value() {
+        let command = parse(&["prog", "--port=8080,9090", "--flag", "--name=x"]);
+        assert_eq!(command.get_parsed::<u16>("--port"), Some(Ok(8080)));
+        assert_eq!(command.get_parsed::<u16>("flag"), None);
+        assert_eq!(command.get_parsed::<u16>("missing"), None);
+        assert!(matches!(command.get_parsed::<u16>("name"), Some(Err(_))));
+    }
+
+    #[test]
+    fn get_parsed_all_spans_occurrences() {
+        let command = parse(&["prog", "--ids=1,2,3", "--ids=4", "--bad=1,x", "--flag"]);
+        assert_eq!(
+            command.get_parsed_all::<u32>("ids"),
+            Some(Ok(vec![1, 2, 3, 4]))
+        );
+        assert_eq!(command.get_parsed_all::<u32>("flag"), Some(Ok(Vec::new())));
+        assert_eq!(command.get_parsed_all::<u32>("missing"), None);
+        assert!(matches!(command.get_parsed_all::<u32>("bad"), Some(Err(_))));
+    }
+
+    #[test]
+    fn parse_from_with_delimiter_splits_on_custom_char() {
+        let command = Command::parse_from_with_delimiter(["prog", "--path=/a:/b:", "--x=a,b"], ':');
+        assert_eq!(command.opts[0].opt_values, ["/a", "/b"]);
+        assert_eq!(command.opts[1].opt_values, ["a,b"]);
+    }
+
+    #[test]
+    fn list_delimiter_keeps_trimming_and_filtering() {
+        let command = Command::parse_from_with_delimiter(["prog", "--path= /a ;; /b ;"], ';');
+        assert_eq!(command.opts[0].opt_values, ["/a", "/b"]);
+    }
+
+    #[test]
+    fn quoted_values_keep_delimiters() {
+        let command = parse(&["prog", r#"--msg="hello,world",bye"#]);
+        assert_eq!(command.opts[0].opt_values, ["hello,world", "bye"]);
+
+        let command = parse(&["prog", r#"--msg= " padded " , x"y,z"w ,"""#]);
+        assert_eq!(command.opts[0].opt_values, [" padded ", "xy,zw", ""]);
+    }
+
+    #[test]
+    fn unterminated_quote_takes_rest_of_value() {
+        let command = parse(&["prog", r#"--msg=a,"b,c"#]);
+        assert_eq!(command.opts[0].opt_values, ["a", "b,c"]);
+    }
+
+    #[test]
+    fn type_iterators_filter_opts() {
+        let command = parse(&["program", "-v", "file.txt", "--data=x", "--", "rest"]);
+        let simple: Vec<&str> = command.simple_args().map(|o| o.opt_str.as_str()).collect();
+        let long: Vec<&str> = command.long_opts().map(|o| o.opt_str.as_str()).collect();
+        let short: Vec<&str> = command.short_opts().map(|o| o.opt_str.as_str()).collect();
+        assert_eq!(simple, ["file.txt"]);
+        assert_eq!(long, ["--data"]);
+        assert_eq!(short, ["-v"]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trips_command() {
+        let config = ParseConfig {
+            multi_group: true,
+            annotation_prefix: Some('@'),
+            ..ParseConfig::default()
+        };
+        let command = Command::parse_with_config(
+            [
+                "prog",
+                "-ab",
+                "--data=x,y",
+                "--list+=z",
+                "@note",
+                "file",
+                "--",
+                "a",
+                "--",
+                "b",
+            ],
+            &config,
+        );
+        let json = serde_json::to_string(&command).unwrap();
+        assert!(json.contains(r#""opt_type":"short_opt""#));
+        assert!(json.contains(r#""opt_type":"long_opt""#));
+        assert!(json.contains(r#""opt_type":"simple""#));
+
+        let back: Command = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, command);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_option_alias_is_arg() {
+        let arg: crate::cmd_arg::Option = Arg::default();
+        assert_eq!(arg.opt_type, OptionType::Simple);
+    }
+
+    #[test]
+    fn try_parse_from_rejects_empty_long_option_name() {
+        let err = Command::try_parse_from(["prog", "-v", "--=foo"]).unwrap_err();
+        assert_eq!(err, ParseError::EmptyLongOptionName("--=foo".to_string()));
+        assert_eq!(err.to_string(), "long option `--=foo` has an empty name");
+        assert!(matches!(
+            Command::try_parse_from(["prog", "--+=x"]),
+            Err(ParseError::EmptyLongOptionName(_))
+        ));
+    }
+
+    #[test]
+    fn try_parse_from_rejects_malformed_options() {
+        let err = Command::try_parse_from(["prog", "---verbose"]).unwrap_err();
+        assert_eq!(err, ParseError::MalformedOption("---verbose".to_string()));
+    }
+
+    #[test]
+    fn try_parse_from_accepts_well_formed_input() {
+        let command =
+            Command::try_parse_from(["prog", "--data=a", "-v", "--", "--=foo", "---x"]).unwrap();
+        assert_eq!(opt_strs(&command), ["--data", "-v"]);
+        assert_eq!(command.args, ["--=foo", "---x"]);
+    }
+
+    #[test]
+    fn empty_long_option_name_is_simple() {
+        let command = parse(&["prog", "--=x", "--=", "--foo=", "--", "--=y"]);
+        assert_eq!(opt_strs(&command), ["--=x", "--=", "--foo"]);
+        assert_eq!(command.opts[0].opt_type, OptionType::Simple);
+        assert!(command.opts[0].opt_values.is_empty());
+        assert_eq!(command.opts[1].opt_type, OptionType::Simple);
+        assert_eq!(command.opts[2].opt_type, OptionType::LongOpt);
+        assert!(command.opts[2].opt_values.is_empty());
+        assert_eq!(command.args, ["--=y"]);
+    }
+
+    fn negative_numbers() -> ParseConfig {
+        ParseConfig {
+            negative_numbers: true,
+            value_shorts: vec!['n'],
+            ..ParseConfig::default()
+        }
+    }
+
+    #[test]
+    fn negative_numbers_are_simple() {
+        let command =
+            Command::parse_with_config(["calc", "-5", "-0.5", "-1e3", "-.5"], &negative_numbers());
+        assert_eq!(opt_strs(&command), ["-5", "-0.5", "-1e3", "-.5"]);
+        assert!(
+            command
+                .opts
+                .iter()
+                .all(|o| o.opt_type == OptionType::Simple)
+        );
+    }
+
+    #[test]
+    fn negative_numbers_keep_short_options() {
+        let command =
+            Command::parse_with_config(["calc", "-v", "-5x", "-nan"], &negative_numbers());
+        assert_eq!(opt_strs(&command), ["-v", "-5", "-x", "-n"]);
+        assert_eq!(command.opts[3].opt_values, ["an"]);
+
+        let command = Command::parse_with_config(["calc", "-n", "-5"], &negative_numbers());
+        assert_eq!(opt_strs(&command), ["-n"]);
+        assert_eq!(command.opts[0].opt_values, ["-5"]);
+
+        let command = parse(&["calc", "-5"]);
+        assert_eq!(command.opts[0].opt_type, OptionType::ShortOpt);
+    }
+
+    #[test]
+    fn short_option_equals_value_matrix() {
+        let cases: &[(&str, &[&str], &[&str])] = &[
+            ("-o=file.txt", &["-o"], &["file.txt"]),
+            ("-o=a,b", &["-o"], &["a", "b"]),
+            ("-ab=val", &["-a", "-b"], &["val"]),
+            ("-o=", &["-o"], &[]),
+            ("-=x", &["-=x"], &[]),
+        ];
+        for (arg, flags, values) in cases {
+            let command = parse(&["prog", arg, "next"]);
+            let mut expected = flags.to_vec();
+            expected.push("next");
+            assert_eq!(opt_strs(&command), expected, "{}", arg);
+            let last = &command.opts[flags.len() - 1];
+            assert_eq!(last.opt_values, *values, "{}", arg);
+            assert!(
+                command.opts[..flags.len() - 1]
+                    .iter()
+                    .all(|o| o.opt_values.is_empty())
+            );
+        }
+    }
+
+    #[test]
+    fn to_plain_string_has_no_escapes() {
+        let command = parse(&["prog", "-v", "--data=a,b", "--", "rest"]);
+        let plain = command.to_plain_string();
+        let opt_plain = command.opts[1].to_plain_string();
+
+        assert!(!plain.contains("\x1b["));
+        assert!(plain.contains("  2. --data (Type: Long Option): Values: [a, b]\n"));
+        assert_eq!(opt_plain, "--data (Type: Long Option): Values: [a, b]");
+    }
+
+    #[test]
+    fn to_json_escapes_strings() {
+        let command = Command {
+            cmd_name: "C:\\bin\\prog".to_string(),
+            ..parse(&["prog", "--msg=line\nbreak\u{1}", "--", "say \"hi\"\t"])
+        };
+        let json = command.to_json();
+        assert!(json.contains(r#""values":["line\nbreak\u0001"]"#));
+        assert!(json.contains(r#""args":["say \"hi\"\t"]"#));
+        assert!(json.contains(r#""cmd_name":"C:\\bin\\prog""#));
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["cmd_name"], "C:\\bin\\prog");
+        assert_eq!(value["opts"][0]["type"], "long_opt");
+        assert_eq!(value["opts"][0]["str"], "--msg");
+        assert_eq!(value["opts"][0]["values"][0], "line\nbreak\u{1}");
+        assert_eq!(value["args"][0], "say \"hi\"\t");
+    }
+
+    #[test]
+    fn commands_compare_and_clone() {
+        let command = parse(&["prog", "-v", "--data=a,b", "--", "rest"]);
+        let snapshot = command.clone();
+        assert_eq!(command, snapshot);
+        assert_eq!(
+            command.opts[1],
+            Arg {
+                opt_type: OptionType::LongOpt,
+                opt_str: "--data".to_string(),
+                opt_values: vec!["a".to_string(), "b".to_string()],
+                append: false,
+                origin_index: 2,
+            }
+        );
+
+        let mut changed = snapshot.clone();
+        changed.args.clear();
+        assert_ne!(command, changed);
+    }
+
+    #[test]
+    fn plus_options_are_opt_in() {
+        let args = ["prog", "+a", "+abc", "-b", "+"];
+        let command = Command::parse_from(args);
+        assert!(
+            command
+                .opts
+                .iter()
+                .all(|o| o.opt_type != OptionType::PlusOpt)
+        );
+        assert_eq!(command.opts[0].opt_str, "+a");
+
+        let config = ParseConfig {
+            plus_options: true,
+            ..ParseConfig::default()
+        };
+        let command = Command::parse_with_config(args, &config);
+        let parsed: Vec<(&str, &OptionType)> = command
+            .opts
+            .iter()
+            .map(|o| (o.opt_str.as_str(), &o.opt_type))
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                ("+a", &OptionType::PlusOpt),
+                ("+a", &OptionType::PlusOpt),
+                ("+b", &OptionType::PlusOpt),
+                ("+c", &OptionType::PlusOpt),
+                ("-b", &OptionType::ShortOpt),
+                ("+", &OptionType::Simple),
+            ]
+        );
+        assert!(command.has_flag("+b"));
+        assert!(command.has_flag("b"));
+        assert_eq!(command.count_flag("+a"), 2);
+        assert_eq!(command.count_flag("a"), 0);
+        assert!(command.opts[0].to_plain_string().contains("Plus Option"));
+    }
+
+    #[test]
+    fn get_all_values_concatenates_occurrences() {
+        let command = parse(&["prog", "--D=X", "-v", "--D=Y,Z", "--E"]);
+        assert_eq!(command.get_all_values("--D"), ["X", "Y", "Z"]);
+        assert_eq!(command.get_all_values("D"), ["X", "Y", "Z"]);
+        assert_eq!(command.get_values("D").unwrap(), ["X"]);
+        assert!(command.get_all_values("E").is_empty());
+        assert!(command.get_all_values("missing").is_empty());
+    }
+
+    #[test]
+    fn multi_value_longs_consume_following_simple_args() {
+        let config = ParseConfig {
+            multi_value_longs: vec!["--files".to_string()],
+            value_longs: vec!["out".to_string()],
+            long_values_require_equals: false,
+            ..ParseConfig::default()
+        };
+        let command = Command::parse_with_config(
+            [
+                "prog",
+                "--files",
+                "a",
+                "b",
+                "--verbose",
+                "--out",
+                "o",
+                "c",
+                "--files",
+                "--",
+                "d",
+            ],
+            &config,
+        );
+        assert_eq!(
+            opt_strs(&command),
+            ["--files", "--verbose", "--out", "c", "--files"]
+        );
+        assert_eq!(command.opts[0].opt_values, ["a", "b"]);
+        assert!(command.opts[1].opt_values.is_empty());
+        assert_eq!(command.opts[2].opt_values, ["o"]);
+        assert!(command.opts[4].opt_values.is_empty());
+        assert_eq!(command.args, ["d"]);
+
+        let config = ParseConfig {
+            multi_value_longs: vec!["files".to_string()],
+            ..ParseConfig::default()
+        };
+        let command =
+            Command::parse_with_config(["prog", "--files=x", "y", "--files", "p,q", "r"], &config);
+        assert_eq!(opt_strs(&command), ["--files", "y", "--files"]);
+        assert_eq!(command.opts[2].opt_values, ["p", "q", "r"]);
+    }
+
+    #[test]
+    fn borrowed_parse_follows_config() {
+        let config = ParseConfig {
+            value_shorts: vec!['n'],
+            value_longs: vec!["--out".to_string()],
+            multi_value_longs: vec!["--files".to_string()],
+            annotation_prefix: Some('@'),
+            structured_options: true,
+            multi_group: true,
+            plus_options: true,
+            negative_numbers: true,
+            ..ParseConfig::default()
+        };
+        let line = [
+            "prog",
+            "@note",
+            "-vn",
+            "5",
+            "--out",
+            "o.txt",
+            "--files",
+            "a",
+            "b",
+            "+x",
+            "-1",
+            "--define:k=v",
+            "--",
+            "c",
+            "--",
+            "d",
+        ];
+        let borrowed = CommandRef::parse_with_config(&line, &config);
+        assert!(matches!(borrowed.annotations[0], Cow::Borrowed("note")));
+        assert_eq!(
+            borrowed.into_command(),
+            Command::parse_with_config(line, &config)
+        );
+    }
+
+    #[test]
+    fn borrowed_parse_matches_owned_parse() {
+        let lines: [&[&str]; 6] = [
+            &[
+                "prog",
+                "-abc",
+                "--data=a, b,,c",
+                "file.txt",
+                "-",
+                "--",
+                "-x",
+                "--y=1",
+            ],
+            &[
+                "prog",
+                "-ab=1,2",
+                "--list+=x",
+                "--=x",
+                "--+=y",
+                "--data =z",
+                "--bare",
+            ],
+            &["prog", r#"--msg="hello,world",bye"#, "-é", "-=x"],
+            &["prog", "--", "--"],
+            &["prog"],
+            &[],
+        ];
+        for line in lines {
+            let borrowed = CommandRef::parse_from_slice(line);
+            assert_eq!(
+                borrowed.to_command(),
+                Command::parse_from(line.iter().copied())
+            );
+        }
+
+        let owned: Vec<String> = ["prog", "-ab", "--data=x,y"].map(String::from).to_vec();
+        let borrowed = CommandRef::parse_from_slice(&owned);
+        assert!(matches!(borrowed.opts[0].opt_str, Cow::Borrowed("-a")));
+        assert!(matches!(borrowed.opts[1].opt_str, Cow::Owned(_)));
+        assert!(matches!(borrowed.opts[2].opt_values[1], Cow::Borrowed("y")));
+    }
+
+    #[test]
+    fn to_command_line_round_trips() {
+        for line in [
+            &[
+                "prog",
+                "-abc",
+                "--data=a,b",
+                "file name.txt",
+                "-",
+                "--",
+                "-x",
+                "it's",
+            ][..],
+            &[
+                "prog",
+                "-ab=1,2",
+                "--list+=x",
+                "--msg=\"a,b\", c ",
+                "--=x",
+                "--e=\"\"",
+            ],
+            &["prog", "--flag", "$HOME", "--", "--"],
+            &["prog", "-x-y", "-a-=v", "-b--"],
+            &["prog"],
+        ] {
+            let without_origins = |mut command: Command| {
+                command.opts.iter_mut().for_each(|o| o.origin_index = 0);
+                command
+            };
+            let command = parse(line);
+            let words = shell_split(&command.to_command_line()).unwrap();
+            assert_eq!(
+                without_origins(Command::parse_from(words)),
+                without_origins(command),
+                "{:?}",
+                line
+            );
+        }
+        assert_eq!(
+            parse(&["prog", "-ab=1", "--x+=a b", "--", "y"]).to_command_line(),
+            r#"prog -a -b=1 "--x+=a b" -- y"#
+        );
+    }
+
+    #[test]
+    fn stdin_marker_is_found_before_and_after_separator() {
+        let stdin = parse(&["program", "-"]);
+        assert!(stdin.has_stdin_marker());
+        assert_eq!(stdin.stdin_positions(), [0]);
+
+        let trailing = parse(&["program", "--", "-"]);
+        assert!(trailing.has_stdin_marker());
+        assert_eq!(trailing.stdin_positions(), [0]);
+
+        let mixed = parse(&["program", "file", "-v", "-"]);
+        assert_eq!(mixed.stdin_positions(), [1]);
+
+        let none = parse(&["program", "--", "file", "--"]);
+        assert!(!none.has_stdin_marker());
+        assert!(none.stdin_positions().is_empty());
+    }
+
+    #[test]
+    fn case_insensitive_lookups_fold_ascii_only() {
+        let command = parse(&["prog", "--Data=x", "-V", "--ÄRGER"]);
+        assert_eq!(command.get_values_ci("data").unwrap(), ["x"]);
+        assert_eq!(command.get_values_ci("--DATA").unwrap(), ["x"]);
+        assert_eq!(command.get_values("data"), None);
+        assert!(command.has_flag_ci("v"));
+        assert!(!command.has_flag("v"));
+        assert!(command.has_flag_ci("--Ärger"));
+        assert!(!command.has_flag_ci("ärger"));
+    }
+
+    #[test]
+    fn windows_style_options_map_to_unix_types() {
+        let args = [
+            "prog",
+            "/v",
+            "/out:a,b",
+            "/etc/passwd",
+            "/Verbose",
+            "/",
+            "--",
+            "/x",
+        ];
+        let command = Command::parse_windows_style(args);
+        assert_eq!(
+            opt_strs(&command),
+            ["-v", "--out", "/etc/passwd", "--Verbose", "/"]
+        );
+        assert_eq!(command.opts[0].opt_type, OptionType::ShortOpt);
+        assert_eq!(command.opts[1].opt_type, OptionType::LongOpt);
+        assert_eq!(command.opts[1].opt_values, ["a", "b"]);
+        assert_eq!(command.opts[2].opt_type, OptionType::Simple);
+        assert_eq!(command.args, ["/x"]);
+        assert!(command.has_flag("v") && command.has_flag_ci("verbose"));
+
+        let unix = Command::parse_from(args);
+        assert!(unix.opts.iter().all(|o| o.opt_type == OptionType::Simple));
+    }
+
+    #[test]
+    fn subcommand_splits_after_global_flags() {
+        let status = parse(&["tool", "status"]);
+        let (name, sub) = status.subcommand().unwrap();
+        assert_eq!(name, "status");
+        assert_eq!(sub.cmd_name, "status");
+        assert!(sub.opts.is_empty());
+
+        let commit = parse(&["tool", "-v", "commit", "--amend", "file", "--", "x"]);
+        let (name, sub) = commit.subcommand().unwrap();
+        assert_eq!(name, "commit");
+        assert_eq!(opt_strs(&sub), ["--amend", "file"]);
+        assert_eq!(sub.args, ["x"]);
+        assert_eq!(sub.subcommand().unwrap().0, "file");
+
+        assert!(
+            parse(&["tool", "-v", "--", "status"])
+                .subcommand()
+                .is_none()
+        );
+        assert!(parse(&["tool"]).subcommand().is_none());
+    }
+
+    #[test]
+    fn bool_flag_uses_last_of_pair() {
+        assert_eq!(parse(&["prog", "--color"]).bool_flag("color"), Some(true));
+        assert_eq!(
+            parse(&["prog", "--no-color"]).bool_flag("--color"),
+            Some(false)
+        );
+        assert_eq!(
+            parse(&["prog", "--no-color", "-v", "--color"]).bool_flag("color"),
+            Some(true)
+        );
+        assert_eq!(
+            parse(&["prog", "--color", "--no-color"]).bool_flag("color"),
+            Some(false)
+        );
+        assert_eq!(
+            parse(&["prog", "-v", "--", "--color"]).bool_flag("color"),
+            None
+        );
+    }
+
+    #[test]
+    fn summary_counts_each_kind() {
+        let command = parse(&["prog", "-ab", "--data=x", "file.txt", "--", "rest"]);
+        assert_eq!(
+            command.summary(),
+            "prog [2 short] [1 long] [1 simple] [1 after --]"
+        );
+        let config = ParseConfig {
+            plus_options: true,
+            ..ParseConfig::default()
+        };
+        let plus = Command::parse_with_config(["prog", "+ab"], &config);
+        assert_eq!(
+            plus.summary(),
+            "prog [0 short] [0 long] [0 simple] [0 after --] [2 plus]"
+        );
+    }
+
+    #[test]
+    fn non_ascii_short_options_bundle_by_char() {
+        for (args, expected) in [
+            (&["prog", "-é"][..], &["-é"][..]),
+            (&["prog", "-aé"], &["-a", "-é"]),
+            (&["prog", "-ébc"], &["-é", "-b", "-c"]),
+            (&["prog", "-日=x"], &["-日"]),
+        ] {
+            let command = parse(args);
+            assert_eq!(opt_strs(&command), expected);
+            assert!(
+                command
+                    .opts
+                    .iter()
+                    .all(|o| o.opt_type == OptionType::ShortOpt)
+            );
+            assert_eq!(CommandRef::parse_from_slice(args).to_command(), command);
+        }
+        assert_eq!(parse(&["prog", "-日=x"]).opts[0].opt_values, ["x"]);
+        assert_eq!(parse(&["prog", "-aé=x"]).opts[1].opt_values, ["x"]);
+    }
+
+    #[test]
+    fn get_value_or_env_prefers_command_line() {
+        // Cargo sets CARGO_PKG_NAME for test runs, so no test has to modify the environment.
+        let from_env = parse(&["prog", "--port"]);
+        assert_eq!(
+            from_env
+                .get_value_or_env("port", "CARGO_PKG_NAME")
+                .as_deref(),
+            Some(env!("CARGO_PKG_NAME"))
+        );
+        let from_cli = parse(&["prog", "--port=8080"]);
+        assert_eq!(
+            from_cli
+                .get_value_or_env("port", "CARGO_PKG_NAME")
+                .as_deref(),
+            Some("8080")
+        );
+        assert_eq!(
+            from_env.get_value_or_env("port", "CMD_ARG_TEST_UNSET"),
+            None
+        );
+    }
+
+    #[test]
+    fn command_builds_from_iterator_of_args() {
+        let parsed = parse(&["prog", "-v", "--data=a,b", "file"]);
+        let mut built: Command = parsed.opts.iter().take(2).cloned().collect();
+        built.cmd_name = "prog".to_string();
+        built.extend([Arg {
+            opt_str: "file".to_string(),
+            origin_index: 3,
+            ..Arg::default()
+        }]);
+        assert_eq!(built, parsed);
+        assert_eq!(opt_strs(&built), ["-v", "--data", "file"]);
+    }
+
+    #[test]
+    fn sorted_opts_orders_by_type_then_name() {
+        let command = parse(&[
+            "prog",
+            "--zeta",
+            "b.txt",
+            "-x",
+            "--alpha=2",
+            "a.txt",
+            "-c",
+            "--alpha=1",
+        ]);
+        let sorted: Vec<(&str, &[String])> = command
+            .sorted_opts()
+            .iter()
+            .map(|o| (o.opt_str.as_str(), o.opt_values.as_slice()))
+            .collect();
+        let names: Vec<&str> = sorted.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            ["a.txt", "b.txt", "-c", "-x", "--alpha", "--alpha", "--zeta"]
+        );
+        assert_eq!(sorted[4].1, ["2"]);
+        assert_eq!(sorted[5].1, ["1"]);
+        assert_eq!(command.opts[0].opt_str, "--zeta");
+    }
+
+    #[test]
+    fn equals_is_never_a_short_option() {
+        for args in [
+            &["prog", "-n=5"][..],
+            &["prog", "-=5"],
+            &["prog", "-ab=x=y"],
+            &["prog", "-="],
+        ] {
+            let command = parse(args);
+            assert!(
+                !command
+                    .opts
+                    .iter()
+                    .any(|o| o.opt_type == OptionType::ShortOpt && o.opt_str.contains('=')),
+                "{:?}",
+                args
+            );
+            assert_eq!(CommandRef::parse_from_slice(args).to_command(), command);
+        }
+        assert_eq!(parse(&["prog", "-n=5"]).opts[0].opt_values, ["5"]);
+        assert_eq!(parse(&["prog", "-=5"]).opts[0].opt_type, OptionType::Simple);
+    }
+
+    #[test]
+    fn accessors_reflect_parsed_structure() {
+        let command = parse(&["program", "-v", "--", "a", "b", "c"]);
+        assert_eq!(command.args_after_double_dash(), ["a", "b", "c"]);
+        assert_eq!(opt_strs(&command), ["-v"]);
+        assert_eq!(command.opts_slice()[0].opt_str, "-v");
+        assert_eq!(command.into_args(), ["a", "b", "c"]);
+
+        let none = parse(&["program", "a"]);
+        assert!(none.args_after_double_dash().is_empty());
+        assert_eq!(none.opts_slice().len(), 1);
+    }
+
+    #[test]
+    fn wants_help_and_version_match_exact_flags() {
+        assert!(parse(&["prog", "-h"]).wants_help());
+        assert!(parse(&["prog", "file", "--help", "-v"]).wants_help());
+        assert!(parse(&["prog", "-V"]).wants_version());
+        assert!(parse(&["prog", "--version"]).wants_version());
+
+        let neither = parse(&["prog", "--helpme", "-v", "--versions", "--", "--help"]);
+        assert!(!neither.wants_help());
+        assert!(!neither.wants_version());
+        assert!(!parse(&["prog", "--version"]).wants_help());
+    }
+
+    #[test]
+    fn origin_index_points_at_source_token() {
+        let config = ParseConfig {
+            value_shorts: vec!['o'],
+            ..ParseConfig::default()
+        };
+        let command = Command::parse_with_config(
+            [
+                "prog",
+                "-abc",
+                "file",
+                "-o",
+                "out",
+                "--data=x,y",
+                "-bo=z",
+                "--",
+                "rest",
+            ],
+            &config,
+        );
+        let origins: Vec<(&str, usize)> = command
+            .opts
+            .iter()
+            .map(|o| (o.opt_str.as_str(), o.origin_index))
+            .collect();
+        assert_eq!(
+            origins,
+            [
+                ("-a", 1),
+                ("-b", 1),
+                ("-c", 1),
+                ("file", 2),
+                ("-o", 3),
+                ("--data", 5),
+                ("-b", 6),
+                ("-o", 6),
+            ]
+        );
+        assert_eq!(
+            Command::parse_raw(["prog", "x", "y"]).opts[1].origin_index,
+            2
+        );
+    }
+
+    #[test]
+    fn custom_separator_replaces_double_dash() {
+        let command =
+            Command::parse_from_with_separator(["prog", "-v", "--", "--x=1", ";", "-y", "--"], ";");
+        assert_eq!(opt_strs(&command), ["-v", "--", "--x"]);
+        assert_eq!(command.opts[1].opt_type, OptionType::LongOpt);
+        assert_eq!(command.args, ["-y", "--"]);
+        let config = ParseConfig {
+            separator: ";".to_string(),
+            ..ParseConfig::default()
+        };
+        assert_eq!(
+            Command::try_parse_with_config(["prog", "--", "--x"], &config).unwrap(),
+            Command::parse_with_config(["prog", "--", "--x"], &config)
+        );
+
+        let none = Command::parse_from_with_separator(["prog", "", "--", "-v"], "");
+        assert_eq!(opt_strs(&none), ["", "--", "-v"]);
+        assert!(none.args.is_empty());
+    }
+
+    #[test]
+    fn parse_strict_rejects_options_after_positionals() {
+        let command =
+            Command::parse_strict(["program", "-v", "--data=x", "file", "--", "-x"]).unwrap();
+        assert_eq!(opt_strs(&command), ["-v", "--data", "file"]);
+
+        assert_eq!(
+            Command::parse_strict(["program", "file", "-vq"]).unwrap_err(),
+            ParseError::OptionAfterPositional("-vq".to_string())
+        );
+        assert_eq!(opt_strs(&parse(&["program", "file", "-v"])), ["file", "-v"]);
+        assert!(matches!(
+            Command::parse_strict(["program", "---x"]),
+            Err(ParseError::MalformedOption(_))
+        ));
+    }
+
+    #[test]
+    fn validate_required_lists_missing_flags() {
+        let command = parse(&["prog", "-v", "--input=a", "--", "--output"]);
+        assert_eq!(command.validate_required(&["v", "--input"]), Ok(()));
+        assert_eq!(
+            command.validate_required(&["--output", "input", "-q"]),
+            Err(vec!["--output".to_string(), "-q".to_string()])
+        );
+        assert_eq!(command.validate_required(&[]), Ok(()));
+    }
+
+    #[test]
+    fn bundles_group_shorts_by_source_token() {
+        let command = parse(&["prog", "-ab", "-c", "-d", "file", "--x"]);
+        let groups: Vec<Vec<&str>> = command
+            .bundles()
+            .iter()
+            .map(|group| group.iter().map(|o| o.opt_str.as_str()).collect())
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                vec!["-a", "-b"],
+                vec!["-c"],
+                vec!["-d"],
+                vec!["file"],
+                vec!["--x"]
+            ]
+        );
+        assert!(parse(&["prog"]).bundles().is_empty());
+    }
+
+    #[test]
+    fn value_longs_unify_equals_and_space_forms() {
+        let attached = Command::parse_with_value_longs(["prog", "--output=a,b", "in"], &["output"]);
+        let separate =
+            Command::parse_with_value_longs(["prog", "--output", "a,b", "in"], &["output"]);
+        assert_eq!(
+            attached.opts[0],
+            Arg {
+                origin_index: 1,
+                ..separate.opts[0].clone()
+            }
+        );
+        assert_eq!(separate.opts[0].opt_values, ["a", "b"]);
+        assert_eq!(opt_strs(&separate), ["--output", "in"]);
+
+        let last = Command::parse_with_value_longs(["prog", "--output"], &["--output"]);
+        assert!(last.opts[0].opt_values.is_empty());
+        let unlisted = Command::parse_with_value_longs(["prog", "--log", "x"], &["output"]);
+        assert_eq!(opt_strs(&unlisted), ["--log", "x"]);
+
+        let config = ParseConfig {
+            value_longs: vec!["output".to_string()],
+            long_values_require_equals: false,
+            min_values: vec![("output".to_string(), 1)],
+            ..ParseConfig::default()
+        };
+        assert!(matches!(
+            Command::try_parse_with_config(["prog", "--output"], &config),
+            Err(ParseError::TooFewValues { .. })
+        ));
+    }
+
+    #[test]
+    fn to_map_merges_repeats_and_skips_positionals() {
+        let command = parse(&["prog", "--inc=a", "-v", "file", "--inc=b,c", "--", "x"]);
+        let map = command.to_map();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["--inc"], ["a", "b", "c"]);
+        assert!(map["-v"].is_empty());
+        assert!(!map.contains_key("file") && !map.contains_key("x"));
+    }
+
+    #[test]
+    fn unknown_options_skips_known_and_positionals() {
+        let command = parse(&[
+            "prog",
+            "--verbose",
+            "file",
+            "--xyz=1",
+            "-v",
+            "--",
+            "--other",
+        ]);
+        let unknown = command.unknown_options(&["verbose", "v"]);
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].opt_str, "--xyz");
+        assert_eq!(unknown[0].opt_values, ["1"]);
+        assert!(
+            command
+                .unknown_options(&["verbose", "xyz", "-v"])
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn quote_words_round_trips_through_shell_split() {
+        let words = [
+            "program",
+            "--msg",
+            "a b",
+            "it's",
+            "say \"hi\"",
+            "",
+            "$HOME",
+            "`date`",
+            r"back\slash",
+            "hi!",
+            "don't!",
+            "plain-arg",
+        ]
+        .map(String::from);
+        let line = quote_words(words.clone());
+        assert_eq!(
+            line,
+            r#"program --msg "a b" "it's" "say \"hi\"" "" "\$HOME" "\`date\`" "back\\slash" 'hi!' 'don'\''t!' plain-arg"#
+        );
+        assert_eq!(shell_split(&line).unwrap(), words);
+    }
+
+    #[test]
+    fn parse_from_line_tokenizes_then_classifies() {
+        let line = r#"  prog   -v "a b"  --data=x '' "#;
+        assert_eq!(
+            shell_split(line).unwrap(),
+            ["prog", "-v", "a b", "--data=x", ""]
+        );
+        let command = Command::parse_from_line(line);
+        assert_eq!(command.cmd_name, "prog");
+        assert_eq!(opt_strs(&command), ["-v", "a b", "--data", ""]);
+        assert_eq!(command.opts[2].opt_values, ["x"]);
+
+        assert_eq!(
+            opt_strs(&Command::parse_from_line(r"prog a\ b c\")),
+            ["a b", "c\\"]
+        );
+        let open = Command::parse_from_line(r#"prog -v "open end"#);
+        assert_eq!(opt_strs(&open), ["-v", "open end"]);
+        assert_eq!(Command::parse_from_line(""), Command::default());
+    }
 }
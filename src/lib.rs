@@ -1,4 +1,10 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
 pub mod cmd_arg;
+pub mod parse;
 #[cfg(test)]
 mod tests {
     #[test]
@@ -0,0 +1,1390 @@
+//! The parsing core: argument classification and the parse loop.
+//!
+//! This module only needs `core` and `alloc`, so it stays available when the default
+//! `std` feature is disabled. Reading arguments from the environment and the colored
+//! `Display` output live in the `cmd_arg` module, which requires `std`.
+
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Represents the classification of a command-line argument based on its format.
+/// This enum distinguishes between simple arguments, short options, and long options.
+///
+/// Variants are ordered as declared (`Simple < ShortOpt < LongOpt < PlusOpt`), which
+/// `Command::sorted_opts` relies on for a deterministic order.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OptionType {
+    /// A standard positional argument or any argument that does not conform to option syntax.
+    /// Includes strings not starting with a hyphen (e.g., `input.txt`), a single hyphen (`-`),
+    /// or arguments after the `--` separator.
+    Simple,
+
+    /// An argument starting with a single hyphen followed by one or more characters (e.g., `-v`).
+    /// Supports bundled short options (e.g., `-abc` is parsed as `-a`, `-b`, `-c`).
+    /// Bundles are split by character, not byte, so non-ASCII flags such as `-é` work too.
+    ShortOpt,
+
+    /// An argument starting with two hyphens followed by one or more characters (e.g., `--verbose`).
+    /// May include values attached via an equals sign (e.g., `--output=results.txt`).
+    LongOpt,
+
+    /// An argument starting with a plus sign followed by one or more characters (e.g., `+a`),
+    /// as used by tools like `chattr` to enable what `-a` disables. Only produced when
+    /// [`ParseConfig::plus_options`] is enabled; bundles such as `+abc` are parsed as `+a`,
+    /// `+b`, `+c`.
+    PlusOpt,
+}
+
+/// Implements the `Default` trait for `OptionType`.
+impl Default for OptionType {
+    /// Returns the default variant of `OptionType`, which is `Simple`.
+    ///
+    /// # Returns
+    ///
+    /// `OptionType::Simple`, as it represents the most basic form of a command-line argument.
+    fn default() -> Self {
+        OptionType::Simple
+    }
+}
+
+/// Holds the parsed information for a single command-line argument classified as an option
+/// or a simple argument appearing before the `--` separator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Arg {
+    /// The classification of this argument's format (`Simple`, `ShortOpt`, or `LongOpt`).
+    pub opt_type: OptionType,
+
+    /// The string representation of the option or simple argument.
+    /// - For `ShortOpt` (e.g., `-v`), this is the full string (e.g., `"-v"`).
+    /// - For `LongOpt` without a value (e.g., `--help`), this is the full string (e.g., `"--help"`).
+    /// - For `LongOpt` with a value (e.g., `--data=val`), this is the flag part (e.g., `"--data"`).
+    /// - For `LongOpt` with append syntax (e.g., `--data+=val`), the `+` is dropped (e.g., `"--data"`).
+    /// - For `Simple` arguments (e.g., `file.txt`), this is the full string.
+    pub opt_str: String,
+
+    /// A list of values associated with the option, if any.
+    /// - For `LongOpt` with values (e.g., `--data=v1,v2`), contains the parsed values (e.g., `["v1", "v2"]`).
+    /// - For a `ShortOpt` registered in [`ParseConfig::value_shorts`] (e.g., `-n5`), contains its value.
+    /// - Empty for `Simple`, other `ShortOpt`s, or `LongOpt` without values.
+    pub opt_values: Vec<String>,
+
+    /// Whether the values were given with append syntax (e.g., `--define+=X`).
+    ///
+    /// Append syntax marks values meant to accumulate onto earlier occurrences rather than
+    /// replace them; merging is left to the consumer. The `+` is not part of `opt_str`.
+    pub append: bool,
+
+    /// The index in the parsed argument list of the token this entry came from.
+    ///
+    /// Index 0 is the command name, so the first argument after it is 1. Entries expanded
+    /// from one token share its index (`-a`, `-b`, and `-c` from `-abc`), and a value taken
+    /// from the following argument does not change it. For response files the index refers
+    /// to the expanded list. Entries not produced by parsing, such as those added with
+    /// `Command::set_subcommand`, use 0.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub origin_index: usize,
+}
+
+/// The former name of [`Arg`], kept so existing code continues to compile.
+///
+/// The struct was renamed because `Option` shadowed `core::option::Option` in every module
+/// that imported it.
+#[deprecated(note = "renamed to `Arg` to avoid shadowing `std::option::Option`")]
+pub type Option = Arg;
+
+/// Implements the `Default` trait for `Arg`.
+impl Default for Arg {
+    /// Returns a default `Arg` instance with empty or default values.
+    ///
+    /// # Returns
+    ///
+    /// An `Arg` with:
+    /// - `opt_type`: `OptionType::Simple` (via `OptionType::default()`).
+    /// - `opt_str`: An empty string.
+    /// - `opt_values`: An empty vector.
+    /// - `append`: `false`.
+    /// - `origin_index`: `0`.
+    fn default() -> Self {
+        Arg {
+            opt_type: OptionType::default(),
+            opt_str: String::new(),
+            opt_values: Vec::new(),
+            append: false,
+            origin_index: 0,
+        }
+    }
+}
+
+/// Represents the complete structured result of parsing the command line.
+/// It separates the program name, options/initial simple arguments, and arguments after `--`.
+///
+/// Methods that work with the values of a named long option, such as
+/// [`Command::get_parsed_vec`], only consider `LongOpt` entries: a `ShortOpt` or `Simple`
+/// argument whose text matches `name` is ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Command {
+    /// The name of the executable program, typically the first argument from the environment.
+    pub cmd_name: String,
+
+    /// A vector of parsed options (`ShortOpt`, `LongOpt`) and simple arguments before `--`.
+    pub opts: Vec<Arg>,
+
+    /// A vector of simple arguments appearing after the `--` separator.
+    pub args: Vec<String>,
+
+    /// Argument groups delimited by repeated `--` separators (e.g., `prog a -- b c -- d`).
+    ///
+    /// Only filled when [`ParseConfig::multi_group`] is enabled, in which case `args` stays
+    /// empty. The first group holds the `Simple` arguments before the first `--`, and each
+    /// following group holds the arguments up to the next `--`.
+    pub arg_groups: Vec<Vec<String>>,
+
+    /// Annotations collected from arguments starting with [`ParseConfig::annotation_prefix`],
+    /// stored without the prefix (e.g., `"note"` for `@note`).
+    pub annotations: Vec<String>,
+}
+
+/// Implements the `Default` trait for `Command`.
+impl Default for Command {
+    /// Returns a default `Command` instance with empty fields.
+    ///
+    /// # Returns
+    ///
+    /// A `Command` with:
+    /// - `cmd_name`: An empty string.
+    /// - `opts`: An empty vector.
+    /// - `args`: An empty vector.
+    /// - `arg_groups`: An empty vector.
+    /// - `annotations`: An empty vector.
+    fn default() -> Self {
+        Command {
+            cmd_name: String::new(),
+            opts: Vec::new(),
+            args: Vec::new(),
+            arg_groups: Vec::new(),
+            annotations: Vec::new(),
+        }
+    }
+}
+
+/// Implements the `Extend` trait for `Command`, appending to `opts`.
+///
+/// Together with [`FromIterator`], this is the supported way to build or transform a
+/// `Command` programmatically, e.g. to construct an expected value in a test.
+impl Extend<Arg> for Command {
+    /// Appends every `Arg` from `iter` to `opts`, in order.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The options to append.
+    fn extend<T: IntoIterator<Item = Arg>>(&mut self, iter: T) {
+        for opt in iter {
+            self.add_opt(opt);
+        }
+    }
+}
+
+/// Implements the `FromIterator` trait for `Command`, collecting into `opts`.
+impl FromIterator<Arg> for Command {
+    /// Builds a `Command` whose `opts` are the collected options.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The options to collect.
+    ///
+    /// # Returns
+    ///
+    /// A `Command` with an empty `cmd_name` and the collected `opts`; set `cmd_name`
+    /// afterwards if needed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::parse::{Arg, Command, OptionType};
+    ///
+    /// let command: Command = ["-v", "--force"]
+    ///     .into_iter()
+    ///     .map(|s| Arg {
+    ///         opt_type: if s.starts_with("--") { OptionType::LongOpt } else { OptionType::ShortOpt },
+    ///         opt_str: s.to_string(),
+    ///         ..Arg::default()
+    ///     })
+    ///     .collect();
+    /// assert_eq!(command.opts.len(), 2);
+    /// ```
+    fn from_iter<T: IntoIterator<Item = Arg>>(iter: T) -> Self {
+        let mut command = Command::default();
+        command.extend(iter);
+        command
+    }
+}
+
+/// Settings that control how [`Command::parse_with_config`] interprets arguments.
+///
+/// The default configuration reproduces the behavior of `cmd_arg::get`.
+#[derive(Debug, Clone)]
+pub struct ParseConfig {
+    /// Short option characters that expect a value (e.g., `'n'` for `-n 5`-style flags).
+    ///
+    /// When one of these characters is reached inside a short option token, bundling stops
+    /// and the remainder of the token becomes that option's value:
+    ///
+    /// 1. Characters after the leading `-` are read left to right, each producing its own `ShortOpt`.
+    /// 2. The first value-expecting character ends the bundle. Everything after it, minus one
+    ///    optional leading `=`, is split with `parse_values` into its `opt_values`.
+    /// 3. If nothing follows that character in the token, the next argument becomes its value
+    ///    instead, provided it is a simple argument (not an option and not `--`). Otherwise,
+    ///    the option is kept with empty `opt_values`.
+    /// 4. An `=` reached before any value-expecting character also ends the bundle, whether or
+    ///    not the characters are listed here: the text after it becomes the value of the
+    ///    option just before the `=`. So `-o=a,b` yields `-o` with `["a", "b"]`, and the
+    ///    ambiguous `-ab=val` yields `-a`, then `-b` with `["val"]`. An `=` right after the
+    ///    hyphen (`-=x`) has no option to attach to, so the whole token is a `Simple`
+    ///    argument; the parser never produces a `-=` option.
+    ///
+    /// With `'n'` registered, `-n=5`, `-n5`, and `-n 5` all yield `-n` with `["5"]`, and
+    /// `-xn 5` yields `-x` followed by `-n` with `["5"]`.
+    pub value_shorts: Vec<char>,
+
+    /// Whether every `--` starts a new argument group instead of ending option parsing once.
+    ///
+    /// When enabled, `prog -v a -- b c -- d` fills [`Command::arg_groups`] with
+    /// `[["a"], ["b", "c"], ["d"]]` and leaves `args` empty. Disabled by default.
+    pub multi_group: bool,
+
+    /// A character marking annotation arguments, such as `'@'` for `@note`.
+    ///
+    /// Before `--`, arguments starting with this character are moved into
+    /// [`Command::annotations`] (without the prefix) instead of being classified as options
+    /// or simple arguments. `None` (the default) disables annotations.
+    pub annotation_prefix: core::option::Option<char>,
+
+    /// Minimum value counts per flag, as `(name, count)` pairs (e.g., `("--range", 2)`).
+    ///
+    /// Each occurrence of a listed flag must carry at least `count` values, or
+    /// [`Command::try_parse_with_config`] returns [`ParseError::TooFewValues`]. Names may be
+    /// given with or without leading hyphens. Ignored by the infallible parsers.
+    pub min_values: Vec<(String, usize)>,
+
+    /// Whether long options may carry a structured `name:key=value` payload.
+    ///
+    /// When enabled, a long option whose first `:` comes before any `=` is split on that
+    /// `:`. For `--set:database.host=localhost`, `opt_str` is `"--set"` and `opt_values`
+    /// is `["database.host=localhost"]`, kept whole rather than split on commas. Use
+    /// [`Command::structured_sets`] to read the pairs back. Disabled by default.
+    pub structured_options: bool,
+
+    /// Whether to strip trailing whitespace from the flag part of a `--key=value` option.
+    ///
+    /// A token such as `"--data =x"` (possible through quoting) would otherwise produce the
+    /// flag name `"--data "`. Enabled by default, since such whitespace is never intended.
+    pub trim_flag_names: bool,
+
+    /// Long options that take a value (e.g., `"--output"`), given with or without hyphens.
+    ///
+    /// This set only matters when [`long_values_require_equals`](Self::long_values_require_equals)
+    /// is `false`: a listed option written without `=` then takes the next argument as its
+    /// value, provided that argument is a simple argument (not an option and not `--`), so
+    /// `--output out.txt` yields `--output` with `["out.txt"]`. Empty by default.
+    pub value_longs: Vec<String>,
+
+    /// Whether long option values must be attached with `=` (as in `--output=out.txt`).
+    ///
+    /// Enabled by default, which is the classic behavior: `--output out.txt` leaves
+    /// `out.txt` as a simple argument even if `--output` is listed in
+    /// [`value_longs`](Self::value_longs). When disabled, values for the options in
+    /// `value_longs` are read from the following argument instead, and the `=` form is no
+    /// longer allowed: [`Command::try_parse_with_config`] rejects any `--flag=x` token
+    /// with [`ParseError::UnexpectedValue`], while the infallible parsers still accept it.
+    pub long_values_require_equals: bool,
+
+    /// Characters that separate a long option's flag from its value.
+    ///
+    /// A long option is split on the first occurrence of any listed character, so with
+    /// `['=', ':']` both `--mode=fast` and `--mode:fast` yield `--mode` with `["fast"]`,
+    /// and `--map:a=b` yields `--map` with `["a=b"]`. An empty list disables the split
+    /// entirely. [`structured_options`](Self::structured_options) is checked first and
+    /// takes precedence for `:`. Defaults to `['=']`.
+    pub value_delimiters: Vec<char>,
+
+    /// The character separating multiple values of one option, as in `--data=a,b`.
+    ///
+    /// Values are still trimmed and empty entries dropped, whatever the delimiter. With
+    /// `':'`, `--path=/a:/b:` yields `["/a", "/b"]`. Defaults to `','`.
+    pub list_delimiter: char,
+
+    /// Whether arguments like `-5`, `-3.14`, or `-1e9` are simple arguments rather than
+    /// short options.
+    ///
+    /// An argument counts as a negative number when everything after the `-` starts with a
+    /// digit or `.` and parses as a float, so `-5x` and `-v` remain short options. This
+    /// suits tools taking negative numeric positionals (e.g., `calc -3.14`), and lets
+    /// value-taking options accept negative values (e.g., `-n -5`). Disabled by default,
+    /// since some tools use digit flags such as `-9`.
+    pub negative_numbers: bool,
+
+    /// Whether arguments starting with `+` (e.g., `+a`) are parsed as options.
+    ///
+    /// When enabled, such an argument is split into one `PlusOpt` per character, like a
+    /// short option bundle, so `+abc` yields `+a`, `+b`, and `+c`. Plus options never take
+    /// values. A lone `+` stays `Simple`. Disabled by default, so `+x` is an ordinary
+    /// argument unless a tool opts in.
+    pub plus_options: bool,
+
+    /// Long options that take every following simple argument as a value (e.g., `"--files"`),
+    /// given with or without hyphens.
+    ///
+    /// A listed option written without `=` consumes arguments until the next option, `--`,
+    /// or the end, so `--files a.txt b.txt --verbose` yields `--files` with
+    /// `["a.txt", "b.txt"]`. Each consumed argument is split on
+    /// [`list_delimiter`](Self::list_delimiter) like an attached value. Unlike
+    /// [`value_longs`](Self::value_longs), which takes exactly one argument, this works
+    /// whatever [`long_values_require_equals`](Self::long_values_require_equals) says.
+    /// Empty by default.
+    pub multi_value_longs: Vec<String>,
+
+    /// Whether Windows-style slash options (e.g., `/v` or `/output:file.txt`) are parsed.
+    ///
+    /// When enabled, an argument starting with `/` is an option if the name after the `/`,
+    /// up to an optional `:value`, is non-empty and contains no further `/`. A one-character
+    /// name becomes the `ShortOpt` `-v`, a longer one the `LongOpt` `--output`, so lookups
+    /// work the same as for Unix-style options. The value is split on
+    /// [`list_delimiter`](Self::list_delimiter), so `/out:a,b` yields `["a", "b"]`. Anything
+    /// with a second `/`, such as `/etc/passwd`, stays a `Simple` path, while a single
+    /// segment such as `/tmp` is read as an option. Disabled by default.
+    pub windows_options: bool,
+
+    /// The argument that ends option parsing, after which everything goes to `args`.
+    ///
+    /// Tools and embedded DSLs may prefer another terminator, such as `";"` or `"::"`. The
+    /// separator replaces `--` everywhere, including the group boundaries of
+    /// [`multi_group`](Self::multi_group); a bare `--` is then a `LongOpt` like any other
+    /// argument starting with two hyphens. An empty string means there is no separator:
+    /// every argument is classified, and `args` stays empty. Defaults to `"--"`.
+    pub separator: String,
+}
+
+/// Implements the `Default` trait for `ParseConfig`.
+impl Default for ParseConfig {
+    /// Returns a `ParseConfig` that matches the behavior of `cmd_arg::get`.
+    ///
+    /// # Returns
+    ///
+    /// A `ParseConfig` with:
+    /// - `value_shorts`: An empty vector (no short option takes a value).
+    /// - `multi_group`: `false`.
+    /// - `annotation_prefix`: `None`.
+    /// - `min_values`: An empty vector (no minimums).
+    /// - `structured_options`: `false`.
+    /// - `trim_flag_names`: `true`.
+    /// - `value_longs`: An empty vector (no long option takes a separate value).
+    /// - `long_values_require_equals`: `true`.
+    /// - `value_delimiters`: `['=']`.
+    /// - `list_delimiter`: `','`.
+    /// - `negative_numbers`: `false`.
+    /// - `plus_options`: `false`.
+    /// - `multi_value_longs`: An empty vector (no long option takes several values).
+    /// - `windows_options`: `false`.
+    /// - `separator`: `"--"`.
+    fn default() -> Self {
+        ParseConfig {
+            value_shorts: Vec::new(),
+            multi_group: false,
+            annotation_prefix: None,
+            min_values: Vec::new(),
+            structured_options: false,
+            trim_flag_names: true,
+            value_longs: Vec::new(),
+            long_values_require_equals: true,
+            value_delimiters: vec!['='],
+            list_delimiter: ',',
+            negative_numbers: false,
+            plus_options: false,
+            multi_value_longs: Vec::new(),
+            windows_options: false,
+            separator: "--".to_string(),
+        }
+    }
+}
+
+impl ParseConfig {
+    /// Returns whether the long option `flag` reads its value from the following argument.
+    ///
+    /// # Arguments
+    ///
+    /// * `flag` - The long option as written, without any `=value` part (e.g., `"--output"`).
+    fn takes_separate_value(&self, flag: &str) -> bool {
+        !self.long_values_require_equals && lists_flag(&self.value_longs, flag)
+    }
+
+    /// Splits a long option into its flag and value parts at the first value delimiter.
+    ///
+    /// # Arguments
+    ///
+    /// * `arg` - The long option token (e.g., `"--mode=fast"`).
+    ///
+    /// # Returns
+    ///
+    /// The parts before and after the delimiter, or `None` if `arg` contains none of
+    /// [`value_delimiters`](Self::value_delimiters).
+    pub(crate) fn split_value<'a>(&self, arg: &'a str) -> core::option::Option<(&'a str, &'a str)> {
+        arg.split_once(|c| self.value_delimiters.contains(&c))
+    }
+
+    /// Returns whether the long option `arg` uses the `name:key=value` structured syntax.
+    ///
+    /// # Arguments
+    ///
+    /// * `arg` - The long option token.
+    pub(crate) fn is_structured(&self, arg: &str) -> bool {
+        self.structured_options
+            && arg
+                .split_once(':')
+                .is_some_and(|(name, _)| !name.contains(|c| self.value_delimiters.contains(&c)))
+    }
+
+    /// Classifies an argument before `--` the way the parser treats it.
+    ///
+    /// This refines [`determine_opt_type`]: a long option with an empty name (`--=x`) names
+    /// no option and is `Simple`, as is a short option starting with `=` (`-=x`) and a
+    /// negative number when [`negative_numbers`](Self::negative_numbers) is enabled. An
+    /// argument such as `+a` is a `PlusOpt` when [`plus_options`](Self::plus_options) is
+    /// enabled, and a slash option is a `ShortOpt` or `LongOpt` when
+    /// [`windows_options`](Self::windows_options) is. A bare `--` that is not the
+    /// [`separator`](Self::separator) stays a `LongOpt`.
+    ///
+    /// # Arguments
+    ///
+    /// * `arg` - The argument to classify.
+    pub(crate) fn classify(&self, arg: &str) -> OptionType {
+        match determine_opt_type(arg) {
+            OptionType::LongOpt if arg != "--" && self.long_flag(arg).0.len() <= 2 => {
+                OptionType::Simple
+            }
+            OptionType::ShortOpt if arg.starts_with("-=") => OptionType::Simple,
+            OptionType::ShortOpt if self.negative_numbers && is_negative_number(arg) => {
+                OptionType::Simple
+            }
+            OptionType::Simple if self.plus_options && arg.len() > 1 && arg.starts_with('+') => {
+                OptionType::PlusOpt
+            }
+            OptionType::Simple if let Some((name, _)) = self.slash_option(arg) => {
+                if name.chars().count() == 1 {
+                    OptionType::ShortOpt
+                } else {
+                    OptionType::LongOpt
+                }
+            }
+            opt_type => opt_type,
+        }
+    }
+
+    /// Splits a Windows-style option such as `/output:file.txt` into its name and value.
+    ///
+    /// # Arguments
+    ///
+    /// * `arg` - The argument to inspect.
+    ///
+    /// # Returns
+    ///
+    /// The name and the value, if any, or `None` if
+    /// [`windows_options`](Self::windows_options) is disabled or `arg` is not a slash option.
+    fn slash_option<'a>(
+        &self,
+        arg: &'a str,
+    ) -> core::option::Option<(&'a str, core::option::Option<&'a str>)> {
+        let rest = arg.strip_prefix('/').filter(|_| self.windows_options)?;
+        let (name, value) = match rest.split_once(':') {
+            Some((name, value)) => (name, Some(value)),
+            None => (rest, None),
+        };
+        (!name.is_empty() && !name.contains('/')).then_some((name, value))
+    }
+
+    /// Checks whether `next` may be taken as the value of the option before it.
+    ///
+    /// Only simple arguments qualify, so a following option or the separator is never
+    /// swallowed.
+    fn is_separate_value(&self, next: &str) -> bool {
+        !self.is_separator(next) && self.classify(next) == OptionType::Simple
+    }
+
+    /// Checks whether `arg` is the configured [`separator`](Self::separator).
+    pub(crate) fn is_separator(&self, arg: &str) -> bool {
+        !self.separator.is_empty() && arg == self.separator
+    }
+
+    /// Extracts the flag part of a long option token, as the parser will store it.
+    ///
+    /// # Arguments
+    ///
+    /// * `arg` - The long option token (e.g., `"--list+=a"`).
+    ///
+    /// # Returns
+    ///
+    /// The flag without any value, append marker, or structured payload (e.g., `"--list"`),
+    /// and whether the token carried a value after a delimiter.
+    pub(crate) fn long_flag<'a>(&self, arg: &'a str) -> (&'a str, bool) {
+        if self.is_structured(arg) {
+            (arg.split_once(':').map_or(arg, |(name, _)| name), false)
+        } else if let Some((key, _)) = self.split_value(arg) {
+            let key = if self.trim_flag_names {
+                key.trim_end()
+            } else {
+                key
+            };
+            (key.strip_suffix('+').unwrap_or(key), true)
+        } else {
+            (arg, false)
+        }
+    }
+}
+
+impl Command {
+    /// Creates a new `Command` instance with the specified command name and empty vectors.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd_name` - The name of the command (e.g., the program name).
+    ///
+    /// # Returns
+    ///
+    /// A `Command` instance with the given `cmd_name` and all other fields empty.
+    pub(crate) fn new(cmd_name: String) -> Self {
+        Command {
+            cmd_name,
+            ..Command::default()
+        }
+    }
+
+    /// Adds a parsed `Arg` to the internal `opts` vector.
+    ///
+    /// # Arguments
+    ///
+    /// * `opt` - The `Arg` struct to add, representing a parsed argument before `--`.
+    pub(crate) fn add_opt(&mut self, opt: Arg) {
+        self.opts.push(opt);
+    }
+
+    /// Parses a caller-supplied sequence of arguments exactly as `cmd_arg::get` does.
+    ///
+    /// The first element is taken as the command name. An empty sequence yields a `Command`
+    /// with an empty `cmd_name`, and a single element yields empty `opts` and `args`.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    ///
+    /// # Returns
+    ///
+    /// A `Command` containing the parsed command name, options, and trailing arguments.
+    pub fn parse_from<I, S>(args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Command::parse_with_config(args, &ParseConfig::default())
+    }
+
+    /// Parses arguments like [`Command::parse_from`], with the given short options taking values.
+    ///
+    /// This is shorthand for [`Command::parse_with_config`] with
+    /// [`ParseConfig::value_shorts`] set to `value_shorts`: `-o file.txt`, `-ofile.txt`, and
+    /// `-abo file.txt` all attach `file.txt` to `-o`. A value-taking option at the end of the
+    /// arguments is kept with empty `opt_values`.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    /// * `value_shorts` - The short option characters that take a value (e.g., `&['o', 'f']`).
+    ///
+    /// # Returns
+    ///
+    /// A `Command` containing the parsed command name, options, and trailing arguments.
+    pub fn parse_with_value_opts<I, S>(args: I, value_shorts: &[char]) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let config = ParseConfig {
+            value_shorts: value_shorts.to_vec(),
+            ..ParseConfig::default()
+        };
+        Command::parse_with_config(args, &config)
+    }
+
+    /// Parses arguments like [`Command::parse_from`], with the given long options taking values.
+    ///
+    /// This is shorthand for [`Command::parse_with_config`] with
+    /// [`ParseConfig::value_longs`] set to `value_longs` and
+    /// [`ParseConfig::long_values_require_equals`] disabled, so `--output=x` and
+    /// `--output x` produce the same `Arg`. The next argument is only taken if it is a
+    /// simple argument, and a value-taking option at the end of the arguments is kept with
+    /// empty `opt_values`; to make that an error, list it in [`ParseConfig::min_values`] and
+    /// use `Command::try_parse_with_config`.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    /// * `value_longs` - The long options that take a value, with or without hyphens
+    ///   (e.g., `&["--output"]`).
+    ///
+    /// # Returns
+    ///
+    /// A `Command` containing the parsed command name, options, and trailing arguments.
+    pub fn parse_with_value_longs<I, S>(args: I, value_longs: &[&str]) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let config = ParseConfig {
+            value_longs: value_longs.iter().map(|name| name.to_string()).collect(),
+            long_values_require_equals: false,
+            ..ParseConfig::default()
+        };
+        Command::parse_with_config(args, &config)
+    }
+
+    /// Parses arguments like [`Command::parse_from`], splitting option values on `delim`.
+    ///
+    /// This is shorthand for [`Command::parse_with_config`] with
+    /// [`ParseConfig::list_delimiter`] set to `delim`, so `--path=/a:/b` with `':'` yields
+    /// `["/a", "/b"]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    /// * `delim` - The character separating values.
+    ///
+    /// # Returns
+    ///
+    /// A `Command` containing the parsed command name, options, and trailing arguments.
+    pub fn parse_from_with_delimiter<I, S>(args: I, delim: char) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let config = ParseConfig {
+            list_delimiter: delim,
+            ..ParseConfig::default()
+        };
+        Command::parse_with_config(args, &config)
+    }
+
+    /// Parses arguments like [`Command::parse_from`], also accepting Windows-style options.
+    ///
+    /// This is shorthand for [`Command::parse_with_config`] with
+    /// [`ParseConfig::windows_options`] enabled, so `/v` yields `-v`, `/out:a,b` yields
+    /// `--out` with `["a", "b"]`, and `/etc/passwd` stays a simple argument.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    ///
+    /// # Returns
+    ///
+    /// A `Command` containing the parsed command name, options, and trailing arguments.
+    pub fn parse_windows_style<I, S>(args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let config = ParseConfig {
+            windows_options: true,
+            ..ParseConfig::default()
+        };
+        Command::parse_with_config(args, &config)
+    }
+
+    /// Parses arguments like [`Command::parse_from`], ending option parsing at `sep`.
+    ///
+    /// This is shorthand for [`Command::parse_with_config`] with
+    /// [`ParseConfig::separator`] set to `sep`, so with `";"` the arguments after a `;`
+    /// token go to `args` and `--` is parsed as a `LongOpt`. An empty `sep` disables the
+    /// separator entirely.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    /// * `sep` - The argument that ends option parsing.
+    ///
+    /// # Returns
+    ///
+    /// A `Command` containing the parsed command name, options, and trailing arguments.
+    pub fn parse_from_with_separator<I, S>(args: I, sep: &str) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let config = ParseConfig {
+            separator: sep.to_string(),
+            ..ParseConfig::default()
+        };
+        Command::parse_with_config(args, &config)
+    }
+
+    /// Parses a caller-supplied sequence of arguments according to `config`.
+    ///
+    /// The first element is taken as the command name. The remaining elements are classified
+    /// exactly as `cmd_arg::get` does, with the additions described on [`ParseConfig`].
+    ///
+    /// A long option with an empty name is not an option: `--=x` and `--=` are kept whole as
+    /// `Simple` arguments (the fallible parsers reject them instead). A bare `--` is the
+    /// separator unless [`ParseConfig::separator`] names another one, in which case it is a
+    /// `LongOpt`, and `--foo=` yields the `LongOpt` `--foo` with empty `opt_values`.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    /// * `config` - The settings controlling classification.
+    ///
+    /// # Returns
+    ///
+    /// A `Command` containing the parsed command name, options, and trailing arguments.
+    pub fn parse_with_config<I, S>(args: I, config: &ParseConfig) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Command::parse_classified(args, config, |arg| config.classify(arg))
+    }
+
+    /// Parses arguments like [`Command::parse_with_config`], using `classify` to determine
+    /// the `OptionType` of each argument before `--`.
+    ///
+    /// The arguments are handed to [`CommandRef`] as owned text, so both parsers share one
+    /// loop and converting the result moves the strings instead of copying them.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    /// * `config` - The settings controlling classification.
+    /// * `classify` - Classifies one argument. It must agree with [`ParseConfig::classify`],
+    ///   so that only the cost of classification changes, never the result.
+    ///
+    /// # Returns
+    ///
+    /// A `Command` containing the parsed command name, options, and trailing arguments.
+    pub(crate) fn parse_classified<I, S, F>(args: I, config: &ParseConfig, classify: F) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+        F: FnMut(&str) -> OptionType,
+    {
+        let args = args.into_iter().map(|arg| Cow::<str>::Owned(arg.into()));
+        CommandRef::parse_classified(args, config, classify).into_command()
+    }
+
+    /// Parses a sequence of arguments without interpreting any of them as options.
+    ///
+    /// The first element is taken as the command name and every remaining element becomes a
+    /// `Simple` entry in `opts`, in order. Nothing is special: `-v`, `--x=1`, and even `--`
+    /// are kept verbatim, and `args` stays empty. This is useful for a "literal" subcommand
+    /// or for tools that want the structure without option semantics.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    ///
+    /// # Returns
+    ///
+    /// A `Command` whose `opts` are all `Simple`.
+    pub fn parse_raw<I, S>(args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut args_iter = args.into_iter().map(Into::into);
+        let mut command = Command::new(args_iter.next().unwrap_or_default());
+        for (index, arg) in (1..).zip(args_iter) {
+            command.add_opt(Arg {
+                opt_type: OptionType::Simple,
+                opt_str: arg,
+                opt_values: Vec::new(),
+                append: false,
+                origin_index: index,
+            });
+        }
+        command
+    }
+}
+
+/// Checks whether `names` contains the long option `flag`, ignoring leading hyphens on both.
+fn lists_flag(names: &[String], flag: &str) -> bool {
+    names
+        .iter()
+        .any(|name| name.trim_start_matches('-') == flag.trim_start_matches('-'))
+}
+
+/// Checks whether `arg` is a negative number such as `-5`, `-0.5`, or `-1e3`.
+///
+/// The part after the hyphen must start with a digit or `.` and parse as a float, which
+/// rules out option-like tokens such as `-5x` as well as `-inf` and `-nan`.
+fn is_negative_number(arg: &str) -> bool {
+    arg.strip_prefix('-').is_some_and(|number| {
+        number.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+            && number.parse::<f64>().is_ok()
+    })
+}
+
+/// Determines the classification of a command-line argument based on its format.
+///
+/// This function is used for arguments before the `--` separator. Arguments after `--`
+/// are always treated as `Simple` and are not passed to this function. It looks at the
+/// leading hyphens only; the parser refines the result with the [`ParseConfig`] in use
+/// (e.g., `--=x` names no option and is parsed as `Simple`).
+///
+/// # Arguments
+///
+/// * `arg` - The command-line argument to classify.
+///
+/// # Returns
+///
+/// An `OptionType` indicating whether the argument is `Simple`, `ShortOpt`, or `LongOpt`.
+///
+/// # Examples
+///
+/// ```
+/// use cmd_arg::parse::{determine_opt_type, OptionType};
+///
+/// assert_eq!(determine_opt_type("file.txt"), OptionType::Simple);
+/// assert_eq!(determine_opt_type("-v"), OptionType::ShortOpt);
+/// assert_eq!(determine_opt_type("--verbose"), OptionType::LongOpt);
+/// assert_eq!(determine_opt_type("-"), OptionType::Simple);
+/// ```
+pub fn determine_opt_type(arg: &str) -> OptionType {
+    if arg.starts_with("--") {
+        OptionType::LongOpt
+    } else if arg.starts_with("-") && arg.len() > 1 {
+        OptionType::ShortOpt
+    } else {
+        OptionType::Simple
+    }
+}
+
+/// Parses a delimiter-separated string of values into a vector of trimmed strings.
+///
+/// Used for processing values in long options with `=` (e.g., `--data=v1,v2`).
+/// Empty strings (e.g., from `,,` or trailing delimiters) are filtered out.
+///
+/// Double quotes group text: a delimiter inside quotes does not split, and the quotes
+/// themselves are removed, so `"hello,world",bye` yields `["hello,world", "bye"]`. Quoted
+/// text is kept exactly, including surrounding whitespace, and a quoted empty string (`""`)
+/// is kept as an empty value. An unterminated quote extends to the end of the input.
+///
+/// # Arguments
+///
+/// * `value` - The string containing delimiter-separated values.
+/// * `delimiter` - The character separating values, normally [`ParseConfig::list_delimiter`].
+///
+/// # Returns
+///
+/// A `Vec<String>` of trimmed, non-empty values. Returns an empty vector if the input is empty or contains only whitespace/delimiters.
+///
+/// # Examples
+///
+/// ```
+/// use cmd_arg::parse::parse_values;
+///
+/// assert_eq!(parse_values("v1,v2", ','), vec!["v1", "v2"]);
+/// assert_eq!(parse_values("v1, v2, ", ','), vec!["v1", "v2"]);
+/// assert_eq!(parse_values("/a:/b:", ':'), vec!["/a", "/b"]);
+/// assert_eq!(parse_values(r#""hello,world",bye"#, ','), vec!["hello,world", "bye"]);
+/// assert_eq!(parse_values(r#""open,end"#, ','), vec!["open,end"]);
+/// assert_eq!(parse_values("", ','), vec![] as Vec<String>);
+/// ```
+pub fn parse_values(value: &str, delimiter: char) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut current = String::new();
+    // Byte range of `current` covered by quoted text, from the first to the last quote.
+    let mut quoted: core::option::Option<(usize, usize)> = None;
+    let mut in_quotes = false;
+
+    for c in value.chars() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+            let at = current.len();
+            quoted = Some(quoted.map_or((at, at), |(start, _)| (start, at)));
+        } else if c == delimiter && !in_quotes {
+            push_value(&mut values, &current, quoted.take());
+            current.clear();
+        } else {
+            current.push(c);
+        }
+    }
+    push_value(&mut values, &current, quoted);
+    values
+}
+
+/// Trims one raw value from [`parse_values`] and adds it to `values` unless it is empty.
+///
+/// Whitespace is only trimmed outside the `quoted` byte range, and a value containing
+/// quotes is kept even if empty.
+fn push_value(values: &mut Vec<String>, raw: &str, quoted: core::option::Option<(usize, usize)>) {
+    match quoted {
+        Some((start, end)) => values.push(format!(
+            "{}{}{}",
+            raw[..start].trim_start(),
+            &raw[start..end],
+            raw[end..].trim_end()
+        )),
+        None => {
+            let value = raw.trim();
+            if !value.is_empty() {
+                values.push(value.to_string());
+            }
+        }
+    }
+}
+
+/// A borrowed counterpart of [`Arg`], produced by [`CommandRef::parse_with_config`].
+///
+/// Flags and values borrow from the parsed arguments wherever the text appears verbatim.
+/// Only text the parser has to rebuild is owned: short options after the first in a
+/// bundle (`-b` and `-c` in `-abc`), `+` options, and values containing double quotes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgRef<'a> {
+    /// The classification of the argument, as in [`Arg::opt_type`].
+    pub opt_type: OptionType,
+
+    /// The flag or argument text, as in [`Arg::opt_str`].
+    pub opt_str: Cow<'a, str>,
+
+    /// The values attached to the option, as in [`Arg::opt_values`].
+    pub opt_values: Vec<Cow<'a, str>>,
+
+    /// Whether the values were given with `+=`, as in [`Arg::append`].
+    pub append: bool,
+
+    /// The index of the token this entry came from, as in [`Arg::origin_index`].
+    pub origin_index: usize,
+}
+
+/// An alias for [`ArgRef`], named after the [`Option`] alias of [`Arg`].
+pub type OptionRef<'a> = ArgRef<'a>;
+
+impl ArgRef<'_> {
+    /// Copies this argument into an owned [`Arg`].
+    pub fn to_arg(&self) -> Arg {
+        self.clone().into_arg()
+    }
+
+    /// Converts this argument into an owned [`Arg`], copying only the borrowed text.
+    pub fn into_arg(self) -> Arg {
+        Arg {
+            opt_type: self.opt_type,
+            opt_str: self.opt_str.into_owned(),
+            opt_values: self.opt_values.into_iter().map(Cow::into_owned).collect(),
+            append: self.append,
+            origin_index: self.origin_index,
+        }
+    }
+}
+
+/// A borrowed counterpart of [`Command`] for read-only inspection without copying arguments.
+///
+/// This suits hot paths that parse many command lines only to look at them.
+/// [`Command::parse_with_config`] is built on the same parser, so the result converts to
+/// exactly what it returns for the same arguments and configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandRef<'a> {
+    /// The name of the command, as in [`Command::cmd_name`].
+    pub cmd_name: Cow<'a, str>,
+
+    /// The options and simple arguments before `--`, as in [`Command::opts`].
+    pub opts: Vec<ArgRef<'a>>,
+
+    /// The arguments after `--`, as in [`Command::args`].
+    pub args: Vec<Cow<'a, str>>,
+
+    /// The positional groups, as in [`Command::arg_groups`].
+    pub arg_groups: Vec<Vec<Cow<'a, str>>>,
+
+    /// The annotation arguments, as in [`Command::annotations`].
+    pub annotations: Vec<Cow<'a, str>>,
+}
+
+impl<'a> CommandRef<'a> {
+    /// Parses a slice of arguments with the default [`ParseConfig`], borrowing from it.
+    ///
+    /// The first element is taken as the command name.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    ///
+    /// # Returns
+    ///
+    /// A `CommandRef` whose fields borrow from `args`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cmd_arg::parse::{Command, CommandRef};
+    ///
+    /// let args = ["prog", "--data=a,b", "-v"];
+    /// let command = CommandRef::parse_from_slice(&args);
+    /// assert_eq!(command.opts[0].opt_str, "--data");
+    /// assert_eq!(command.opts[0].opt_values, ["a", "b"]);
+    /// assert_eq!(command.to_command(), Command::parse_from(args));
+    /// ```
+    pub fn parse_from_slice<S: AsRef<str>>(args: &'a [S]) -> CommandRef<'a> {
+        CommandRef::parse_with_config(args, &ParseConfig::default())
+    }
+
+    /// Parses a slice of arguments according to `config`, borrowing from it.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    /// * `config` - The settings controlling classification.
+    ///
+    /// # Returns
+    ///
+    /// A `CommandRef` that converts to what [`Command::parse_with_config`] returns.
+    pub fn parse_with_config<S: AsRef<str>>(args: &'a [S], config: &ParseConfig) -> CommandRef<'a> {
+        let args = args.iter().map(|arg| Cow::Borrowed(arg.as_ref()));
+        CommandRef::parse_classified(args, config, |arg| config.classify(arg))
+    }
+
+    /// Parses arguments that are each either borrowed or owned, using `classify` to
+    /// determine the `OptionType` of each argument before `--`.
+    ///
+    /// Parts of a borrowed argument stay borrowed; parts of an owned argument are copied
+    /// only when the argument is split, and otherwise moved into the result.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to parse, including the command name.
+    /// * `config` - The settings controlling classification.
+    /// * `classify` - Classifies one argument, in agreement with [`ParseConfig::classify`].
+    ///
+    /// # Returns
+    ///
+    /// A `CommandRef` containing the parsed command name, options, and trailing arguments.
+    pub(crate) fn parse_classified<I, F>(
+        args: I,
+        config: &ParseConfig,
+        mut classify: F,
+    ) -> CommandRef<'a>
+    where
+        I: IntoIterator<Item = Cow<'a, str>>,
+        F: FnMut(&str) -> OptionType,
+    {
+        let mut args_iter = args.into_iter().enumerate().peekable();
+        let mut command = CommandRef {
+            cmd_name: args_iter.next().map(|(_, arg)| arg).unwrap_or_default(),
+            opts: Vec::new(),
+            args: Vec::new(),
+            arg_groups: Vec::new(),
+            annotations: Vec::new(),
+        };
+        let mut trailing_groups = Vec::new();
+
+        while let Some((index, arg)) = args_iter.next() {
+            if config.is_separator(&arg) {
+                if config.multi_group {
+                    trailing_groups.push(Vec::new());
+                    for (_, arg) in args_iter {
+                        match trailing_groups.last_mut() {
+                            Some(group) if !config.is_separator(&arg) => group.push(arg),
+                            _ => trailing_groups.push(Vec::new()),
+                        }
+                    }
+                } else {
+                    command.args.extend(args_iter.map(|(_, arg)| arg));
+                }
+                break;
+            }
+
+            if let Some(prefix) = config
+                .annotation_prefix
+                .filter(|prefix| arg.starts_with(*prefix))
+            {
+                command
+                    .annotations
+                    .push(slice_cow(&arg, prefix.len_utf8()..arg.len()));
+                continue;
+            }
+
+            if let Some((name, value)) = config.slash_option(&arg) {
+                let short = name.chars().count() == 1;
+                command.opts.push(ArgRef {
+                    opt_type: if short {
+                        OptionType::ShortOpt
+                    } else {
+                        OptionType::LongOpt
+                    },
+                    opt_str: Cow::Owned(format!("{}{}", if short { "-" } else { "--" }, name)),
+                    opt_values: value
+                        .map(|value| {
+                            cow_values(
+                                &arg,
+                                arg.len() - value.len()..arg.len(),
+                                config.list_delimiter,
+                            )
+                        })
+                        .unwrap_or_default(),
+                    append: false,
+                    origin_index: index,
+                });
+                continue;
+            }
+
+            let opt_type = classify(&arg);
+
+            match opt_type {
+                OptionType::LongOpt => {
+                    if let Some(colon) = arg.find(':').filter(|_| config.is_structured(&arg)) {
+                        command.opts.push(ArgRef {
+                            opt_type: OptionType::LongOpt,
+                            opt_str: slice_cow(&arg, 0..colon),
+                            opt_values: if colon + 1 == arg.len() {
+                                Vec::new()
+                            } else {
+                                vec![slice_cow(&arg, colon + 1..arg.len())]
+                            },
+                            append: false,
+                            origin_index: index,
+                        });
+                    } else if let Some((key, value)) = config.split_value(&arg) {
+                        let key = if config.trim_flag_names {
+                            key.trim_end()
+                        } else {
+                            key
+                        };
+                        let (key, append) = match key.strip_suffix('+') {
+                            Some(key) => (key, true),
+                            None => (key, false),
+                        };
+                        let value_start = arg.len() - value.len();
+                        command.opts.push(ArgRef {
+                            opt_type: OptionType::LongOpt,
+                            opt_str: slice_cow(&arg, 0..key.len()),
+                            opt_values: cow_values(
+                                &arg,
+                                value_start..arg.len(),
+                                config.list_delimiter,
+                            ),
+                            append,
+                            origin_index: index,
+                        });
+                    } else {
+                        let opt_values = if lists_flag(&config.multi_value_longs, &arg) {
+                            let mut values = Vec::new();
+                            while let Some((_, value)) =
+                                args_iter.next_if(|(_, next)| config.is_separate_value(next))
+                            {
+                                values.extend(cow_values(
+                                    &value,
+                                    0..value.len(),
+                                    config.list_delimiter,
+                                ));
+                            }
+                            values
+                        } else if config.takes_separate_value(&arg) {
+                            args_iter
+                                .next_if(|(_, next)| config.is_separate_value(next))
+                                .map(|(_, value)| {
+                                    cow_values(&value, 0..value.len(), config.list_delimiter)
+                                })
+                                .unwrap_or_default()
+                        } else {
+                            Vec::new()
+                        };
+                        command.opts.push(ArgRef {
+                            opt_type: OptionType::LongOpt,
+                            opt_str: arg,
+                            opt_values,
+                            append: false,
+                            origin_index: index,
+                        });
+                    }
+                }
+                OptionType::ShortOpt => {
+                    if command.add_short_bundle(arg, index, config)
+                        && let Some((_, value)) =
+                            args_iter.next_if(|(_, next)| config.is_separate_value(next))
+                        && let Some(opt) = command.opts.last_mut()
+                    {
+                        opt.opt_values = cow_values(&value, 0..value.len(), config.list_delimiter);
+                    }
+                }
+                OptionType::PlusOpt => {
+                    for c in arg[1..].chars() {
+                        command.opts.push(ArgRef {
+                            opt_type: OptionType::PlusOpt,
+                            opt_str: Cow::Owned(format!("+{}", c)),
+                            opt_values: Vec::new(),
+                            append: false,
+                            origin_index: index,
+                        });
+                    }
+                }
+                OptionType::Simple => {
+                    command.opts.push(ArgRef {
+                        opt_type: OptionType::Simple,
+                        opt_str: arg,
+                        opt_values: Vec::new(),
+                        append: false,
+                        origin_index: index,
+                    });
+                }
+            }
+        }
+
+        if config.multi_group {
+            let positionals = command
+                .opts
+                .iter()
+                .filter(|o| o.opt_type == OptionType::Simple)
+                .map(|o| o.opt_str.clone())
+                .collect();
+            command.arg_groups = core::iter::once(positionals)
+                .chain(trailing_groups)
+                .collect();
+        }
+
+        command
+    }
+
+    /// Expands a short option token (e.g., `-abc`) into one `ShortOpt` per character.
+    ///
+    /// Bundling stops at the first character listed in `config.value_shorts`, or at an `=`
+    /// following at least one option character; the rest of the token becomes the value of
+    /// the option just added, as described on [`ParseConfig::value_shorts`].
+    ///
+    /// # Arguments
+    ///
+    /// * `arg` - The short option token, including its leading hyphen.
+    /// * `index` - The position of `arg` in the argument list, recorded as `origin_index`.
+    /// * `config` - The settings that determine which characters expect a value.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the last option added expects a value but the token ended right after it,
+    /// so the value should be taken from the next argument.
+    fn add_short_bundle(&mut self, arg: Cow<'a, str>, index: usize, config: &ParseConfig) -> bool {
+        for (i, c) in arg[1..].char_indices() {
+            let at = 1 + i;
+            if c == '='
+                && i > 0
+                && let Some(last) = self.opts.last_mut()
+            {
+                last.opt_values = cow_values(&arg, at + 1..arg.len(), config.list_delimiter);
+                return false;
+            }
+            let end = at + c.len_utf8();
+            let mut opt = ArgRef {
+                opt_type: OptionType::ShortOpt,
+                opt_str: if i == 0 {
+                    slice_cow(&arg, 0..end)
+                } else {
+                    Cow::Owned(format!("-{}", c))
+                },
+                opt_values: Vec::new(),
+                append: false,
+                origin_index: index,
+            };
+            if config.value_shorts.contains(&c) {
+                let start = if arg[end..].starts_with('=') {
+                    end + 1
+                } else {
+                    end
+                };
+                opt.opt_values = cow_values(&arg, start..arg.len(), config.list_delimiter);
+                self.opts.push(opt);
+                return end == arg.len();
+            }
+            self.opts.push(opt);
+        }
+        false
+    }
+
+    /// Copies the parse result into an owned [`Command`].
+    ///
+    /// # Returns
+    ///
+    /// The `Command` that [`Command::parse_with_config`] returns for the same arguments.
+    pub fn to_command(&self) -> Command {
+        self.clone().into_command()
+    }
+
+    /// Converts the parse result into an owned [`Command`], copying only the borrowed text.
+    ///
+    /// # Returns
+    ///
+    /// The `Command` that [`Command::parse_with_config`] returns for the same arguments.
+    pub fn into_command(self) -> Command {
+        let mut command = Command::new(self.cmd_name.into_owned());
+        command.opts = self.opts.into_iter().map(ArgRef::into_arg).collect();
+        command.args = self.args.into_iter().map(Cow::into_owned).collect();
+        command.arg_groups = self
+            .arg_groups
+            .into_iter()
+            .map(|group| group.into_iter().map(Cow::into_owned).collect())
+            .collect();
+        command.annotations = self.annotations.into_iter().map(Cow::into_owned).collect();
+        command
+    }
+}
+
+/// Returns `text[range]`, borrowing from the parsed arguments when `text` does.
+fn slice_cow<'a>(text: &Cow<'a, str>, range: Range<usize>) -> Cow<'a, str> {
+    match text {
+        Cow::Borrowed(text) => Cow::Borrowed(&text[range]),
+        Cow::Owned(text) => Cow::Owned(text[range].to_string()),
+    }
+}
+
+/// Splits `text[range]` into values like [`parse_values`] does, borrowing from the parsed
+/// arguments when `text` does.
+fn cow_values<'a>(text: &Cow<'a, str>, range: Range<usize>, delimiter: char) -> Vec<Cow<'a, str>> {
+    match text {
+        Cow::Borrowed(text) => borrow_values(&text[range], delimiter),
+        Cow::Owned(text) => parse_values(&text[range], delimiter)
+            .into_iter()
+            .map(Cow::Owned)
+            .collect(),
+    }
+}
+
+/// Splits a value like [`parse_values`] does, borrowing from `value` when it has no quotes.
+fn borrow_values(value: &str, delimiter: char) -> Vec<Cow<'_, str>> {
+    if value.contains('"') {
+        return parse_values(value, delimiter)
+            .into_iter()
+            .map(Cow::Owned)
+            .collect();
+    }
+    value
+        .split(delimiter)
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(Cow::Borrowed)
+        .collect()
+}
+
+/// Parses a slice of arguments with the default [`ParseConfig`].
+///
+/// This is the entry point for `no_std` targets, which have no process environment to read
+/// from. The first element is taken as the command name.
+///
+/// # Arguments
+///
+/// * `args` - The arguments to parse, including the command name.
+///
+/// # Returns
+///
+/// A `Command` containing the parsed command name, options, and trailing arguments.
+pub fn parse_from(args: &[&str]) -> Command {
+    Command::parse_from(args.iter().copied())
+}
@@ -0,0 +1,34 @@
+//! Uses the `parse` module from a `#![no_std]` crate that only links `alloc`.
+//!
+//! The test harness itself still needs `std`, so this checks that the parsing API works
+//! with `core`/`alloc` types alone. The library is checked without `std` by building it
+//! with `cargo build --no-default-features`.
+#![no_std]
+
+extern crate alloc;
+extern crate std;
+
+use alloc::vec;
+use cmd_arg::parse::{OptionType, ParseConfig, parse_from};
+
+#[test]
+fn parses_a_slice_without_std() {
+    let command = parse_from(&["prog", "-ab", "--data=x,y", "file", "--", "rest"]);
+    assert_eq!(command.cmd_name, "prog");
+    assert_eq!(command.opts.len(), 4);
+    assert_eq!(command.opts[0].opt_type, OptionType::ShortOpt);
+    assert_eq!(command.opts[2].opt_str, "--data");
+    assert_eq!(command.opts[2].opt_values, vec!["x", "y"]);
+    assert_eq!(command.opts[3].opt_type, OptionType::Simple);
+    assert_eq!(command.args, vec!["rest"]);
+}
+
+#[test]
+fn parses_with_config_without_std() {
+    let config = ParseConfig {
+        value_shorts: vec!['o'],
+        ..ParseConfig::default()
+    };
+    let command = cmd_arg::parse::Command::parse_with_config(["prog", "-oout.txt"], &config);
+    assert_eq!(command.opts[0].opt_values, vec!["out.txt"]);
+}